@@ -0,0 +1,236 @@
+use pan::{rules::Rules, state::Turn, strategy::Difficulty};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: pan [COMMAND] [OPTIONS]
+
+Commands:
+  play                      Play against the computer (default)
+  multi [SEATS]             Play with more players, e.g. hbbb: a human and three bots
+  solve                     Solve all states and save them to the cache
+  analyze                   Print the outcome of the start position and of every move
+  replay [N]                Step through the N-th game of the record file [default: last]
+  simulate                  Play games between two computer players
+  cache stats [FILE]        Print counts of wins, losses and draws in the cache
+  cache verify [FILE]       Check cached states against their following states
+  cache diff LEFT RIGHT     Compare two caches
+  cache merge OUTPUT INPUT...
+                            Add states of all inputs to the output cache
+
+Options:
+  --cache PATH              Cache file [default: pan_cache-RULES.bin, RULES identifying
+                            the rule variant]
+  --record PATH             File played games are appended to [default: pan_games.txt]
+  --seed N                  Seed of the deal and computer players [default: random]
+  --position POSITION       Start position: random, initial, a position such as
+                            9911JJQQKKAA|-|911JJQQKKAA p (player|table|opponent side
+                            to move, - for no cards) or a file containing one
+                            [default: random]
+  --first player|opponent   Who moves first [default: as dealt]
+  --difficulty DIFFICULTY   Strength of the opponent: random, greedy, epsilon[:P] or optimal
+                            [default: asked when playing, optimal otherwise]
+  --player DIFFICULTY       Strength of the player when simulating [default: optimal]
+  --games N                 Number of simulated games [default: 100]
+  --threads N               Threads solving states [default: available parallelism]
+  --verdicts-only           Keep only who wins in the output of cache merge, 2 bits per
+                            state, too little for optimal play but enough for lookups
+  --hide-opponent           Hide the opponent's hand
+  --take N                  Cards taken from the table stack [default: 3]
+  --put-three               Allow putting three cards of the same rank
+  --no-nine-on-table        Deal all 24 cards instead of starting with a nine on the table
+  --nine-of-hearts-starts   The holder of the nine of hearts opens with it
+  -h, --help                Print this help";
+
+pub enum Command {
+    Help,
+    Play,
+    Multi {
+        seats: String,
+    },
+    Solve,
+    Analyze,
+    /// 1-based number of the game in the record file, `None` for the last one.
+    Replay(Option<usize>),
+    Simulate,
+    Cache(CacheCommand),
+}
+
+pub enum CacheCommand {
+    /// `None` for the `--cache` file.
+    Stats(Option<PathBuf>),
+    Verify(Option<PathBuf>),
+    Diff(PathBuf, PathBuf),
+    Merge {
+        output: PathBuf,
+        inputs: Vec<PathBuf>,
+    },
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Position {
+    Random,
+    Initial,
+    /// Parsed by `VerboseState::parse` once the rules are known.
+    Notation(String),
+    File(PathBuf),
+}
+
+pub struct Options {
+    pub command: Command,
+    /// `None` for the default of the rules, see `cache_path`.
+    pub cache_path: Option<PathBuf>,
+    pub record_path: PathBuf,
+    pub seed: Option<u64>,
+    pub position: Position,
+    /// `None` to keep the turn of the position.
+    pub first: Option<Turn>,
+    /// `None` to ask when playing.
+    pub difficulty: Option<Difficulty>,
+    pub player_difficulty: Difficulty,
+    pub games: usize,
+    pub threads: Option<usize>,
+    pub verdicts_only: bool,
+    pub hide_opponent_hand: bool,
+    pub rules: Rules,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            command: Command::Play,
+            cache_path: None,
+            record_path: PathBuf::from("pan_games.txt"),
+            seed: None,
+            position: Position::Random,
+            first: None,
+            difficulty: None,
+            player_difficulty: Difficulty::Optimal,
+            games: 100,
+            threads: None,
+            verdicts_only: false,
+            hide_opponent_hand: false,
+            rules: Rules::default(),
+        };
+
+        let mut positional = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value of {}", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => options.command = Command::Help,
+                "--cache" => options.cache_path = Some(PathBuf::from(value()?)),
+                "--record" => options.record_path = PathBuf::from(value()?),
+                "--seed" => options.seed = Some(parse_number(&value()?, "seed")?),
+                "--position" => {
+                    options.position = match value()?.as_str() {
+                        "random" => Position::Random,
+                        "initial" => Position::Initial,
+                        p if p.contains('|') => Position::Notation(p.to_string()),
+                        p => Position::File(PathBuf::from(p)),
+                    }
+                }
+                "--first" => {
+                    options.first = match value()?.as_str() {
+                        "player" => Some(Turn::Player),
+                        "opponent" => Some(Turn::Opponent),
+                        t => return Err(format!("Unknown side '{}'", t)),
+                    }
+                }
+                "--difficulty" => options.difficulty = Some(value()?.parse()?),
+                "--player" => options.player_difficulty = value()?.parse()?,
+                "--games" => options.games = parse_number(&value()?, "number of games")?,
+                "--threads" => match parse_number(&value()?, "number of threads")? {
+                    0 => return Err("At least one thread is needed".to_string()),
+                    threads => options.threads = Some(threads),
+                },
+                "--verdicts-only" => options.verdicts_only = true,
+                "--hide-opponent" => options.hide_opponent_hand = true,
+                "--take" => match parse_number(&value()?, "number of cards")? {
+                    0 => return Err("At least one card has to be taken".to_string()),
+                    cnt => options.rules.cards_cnt_when_taking_from_stack = cnt,
+                },
+                "--put-three" => options.rules.allow_put_three = true,
+                "--no-nine-on-table" => options.rules.nine_on_table = false,
+                "--nine-of-hearts-starts" => options.rules.nine_of_hearts_starts = true,
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+                _ => positional.push(arg),
+            }
+        }
+
+        if !matches!(options.command, Command::Help) {
+            options.command = Self::parse_command(positional)?;
+        }
+        Ok(options)
+    }
+
+    /// Cache file of states under given rules.
+    pub fn cache_path(&self, rules: &Rules) -> PathBuf {
+        self.cache_path.clone().unwrap_or_else(|| {
+            let fingerprint = rules.fingerprint().map(|b| format!("{:02x}", b)).concat();
+            PathBuf::from(format!("pan_cache-{}.bin", fingerprint))
+        })
+    }
+
+    fn parse_command(positional: Vec<String>) -> Result<Command, String> {
+        let mut positional = positional.into_iter();
+        let command = positional.next();
+        let rest = positional.collect::<Vec<_>>();
+        let paths = || rest.iter().skip(1).map(PathBuf::from).collect::<Vec<_>>();
+        let no_args = |command| match rest.as_slice() {
+            [] => Ok(command),
+            [arg, ..] => Err(format!("Unexpected argument '{}'", arg)),
+        };
+
+        match command.as_deref() {
+            None | Some("play") => no_args(Command::Play),
+            Some("multi") => match rest.as_slice() {
+                [] => Ok(Command::Multi {
+                    seats: "hbbb".to_string(),
+                }),
+                [seats] => Ok(Command::Multi {
+                    seats: seats.clone(),
+                }),
+                [_, arg, ..] => Err(format!("Unexpected argument '{}'", arg)),
+            },
+            Some("solve") => no_args(Command::Solve),
+            Some("analyze") => no_args(Command::Analyze),
+            Some("replay") => match rest.as_slice() {
+                [] => Ok(Command::Replay(None)),
+                [game] => match parse_number(game, "game number")? {
+                    0 => Err("Games are numbered from 1".to_string()),
+                    game => Ok(Command::Replay(Some(game))),
+                },
+                [_, arg, ..] => Err(format!("Unexpected argument '{}'", arg)),
+            },
+            Some("simulate") => no_args(Command::Simulate),
+            Some("cache") => {
+                let paths = paths();
+                let cache_command = match (rest.first().map(String::as_str), paths.as_slice()) {
+                    (Some("stats"), []) => CacheCommand::Stats(None),
+                    (Some("stats"), [path]) => CacheCommand::Stats(Some(path.clone())),
+                    (Some("verify"), []) => CacheCommand::Verify(None),
+                    (Some("verify"), [path]) => CacheCommand::Verify(Some(path.clone())),
+                    (Some("diff"), [left, right]) => {
+                        CacheCommand::Diff(left.clone(), right.clone())
+                    }
+                    (Some("merge"), [output, inputs @ ..]) if !inputs.is_empty() => {
+                        CacheCommand::Merge {
+                            output: output.clone(),
+                            inputs: inputs.to_vec(),
+                        }
+                    }
+                    _ => return Err("Invalid cache command".to_string()),
+                };
+                Ok(Command::Cache(cache_command))
+            }
+            Some(command) => Err(format!("Unknown command '{}'", command)),
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid {} '{}'", what, s))
+}
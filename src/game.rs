@@ -1,16 +1,35 @@
-use crate::{
+use console_engine::{pixel, Color, ConsoleEngine, KeyCode};
+use pan::{
+    record::GameRecord,
+    rules::Rules,
     state::{CardsHand, Move, MoveDescription, Turn, VerboseState},
-    strategy::{Optimal, OptimalCache, Strategy},
+    strategy::{Difficulty, Optimal, OptimalCache, Strategy},
 };
-use console_engine::{pixel, Color, ConsoleEngine, KeyCode};
-use std::cmp::{max, Ordering};
+use rand::{rngs::StdRng, SeedableRng};
+use std::cmp::{max, Ordering, Reverse};
 
 pub struct Game<'a> {
     state: VerboseState,
+    rules: Rules,
+    /// Used to show the outcome of the state.
     strategy: Optimal<'a>,
+    bot: Box<dyn Strategy + 'a>,
+    difficulty: Difficulty,
+    /// Of the game, together with the start state and player's moves it determines the game.
+    seed: u64,
+    rng: StdRng,
     engine: ConsoleEngine,
+    hide_opponent_hand: bool,
+    record: GameRecord,
+    /// `Some` when stepping through a recorded game instead of playing.
+    replay: Option<Replay>,
+    /// Positions before the player's moves.
+    undo_stack: Vec<Snapshot>,
+    /// Positions left by undo, until the player moves.
+    redo_stack: Vec<Snapshot>,
 
-    last_opponent_move: Option<MoveDescription>,
+    /// Shown above the table stack: the opponent's when playing, any when replaying.
+    last_move: Option<MoveDescription>,
     player_moves: Vec<Move>,
     player_move_sel: Option<usize>,
     game_finished: bool,
@@ -18,13 +37,35 @@ pub struct Game<'a> {
     needs_redrawing: bool,
 }
 
+/// Position at the player's turn together with what is needed to return to it.
+struct Snapshot {
+    state: VerboseState,
+    last_move: Option<MoveDescription>,
+    moves: Vec<MoveDescription>,
+    /// So the bot replies as it did, keeping the game determined by the seed.
+    rng: StdRng,
+}
+
+struct Replay {
+    /// The start position followed by the position after every move.
+    positions: Vec<VerboseState>,
+    ply: usize,
+}
+
 impl<'a> Game<'a> {
     const SCREEN_WIDTH: u32 = 30;
     const SCREEN_HEIGHT: u32 = 16;
     const SCREEN_FPS: u32 = 30;
 
-    pub fn new(state: VerboseState, cache: &'a mut OptimalCache) -> Self {
+    pub fn new(
+        state: VerboseState,
+        cache: &'a mut OptimalCache,
+        difficulty: Difficulty,
+        hide_opponent_hand: bool,
+        seed: u64,
+    ) -> Self {
         let strategy = Optimal::new_with_mut_cache(&state, cache);
+        let bot = difficulty.strategy(strategy);
         let engine = console_engine::ConsoleEngine::init(
             Self::SCREEN_WIDTH,
             Self::SCREEN_HEIGHT,
@@ -32,13 +73,25 @@ impl<'a> Game<'a> {
         )
         .unwrap();
         let game_finished = state.is_game_finished();
+        let rules = *strategy.rules();
+        let record = GameRecord::new(rules, difficulty, seed, state.clone());
 
         Game {
             state,
+            rules,
             strategy,
+            bot,
+            difficulty,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
             engine,
+            hide_opponent_hand,
+            record,
+            replay: None,
+            undo_stack: vec![],
+            redo_stack: vec![],
 
-            last_opponent_move: None,
+            last_move: None,
             player_moves: vec![],
             player_move_sel: None,
             game_finished,
@@ -47,41 +100,118 @@ impl<'a> Game<'a> {
         }
     }
 
-    pub fn run(mut self) {
+    /// Game stepping through the recorded one, evaluating every position.
+    pub fn replay(record: GameRecord, cache: &'a mut OptimalCache) -> Result<Self, String> {
+        let positions = record.positions()?;
+        let mut game = Self::new(
+            record.start.clone(),
+            cache,
+            record.opponent,
+            false,
+            record.seed,
+        );
+        game.record = record;
+        game.replay = Some(Replay { positions, ply: 0 });
+        Ok(game)
+    }
+
+    /// Returns the record of the game, ended when quitting.
+    pub fn run(mut self) -> GameRecord {
         loop {
             self.engine.wait_frame();
             if self.engine.is_key_pressed(KeyCode::Char('q')) {
                 break;
             }
 
-            self.run_logic();
+            if self.replay.is_some() {
+                self.run_replay_logic();
+            } else {
+                self.run_logic();
+            }
 
             if self.needs_redrawing {
                 self.redraw();
                 self.needs_redrawing = false;
             }
         }
+
+        if self.replay.is_none() {
+            self.record.end(self.state.winner());
+        }
+        self.record
+    }
+
+    fn run_replay_logic(&mut self) {
+        let replay = self.replay.as_mut().unwrap();
+        let ply = replay.ply;
+        if self.engine.is_key_pressed(KeyCode::Left) {
+            replay.ply = replay.ply.saturating_sub(1);
+        }
+        if self.engine.is_key_pressed(KeyCode::Right) {
+            replay.ply = (replay.ply + 1).min(replay.positions.len() - 1);
+        }
+        if replay.ply != ply || self.needs_redrawing {
+            self.state = replay.positions[replay.ply].clone();
+            self.last_move = replay.ply.checked_sub(1).map(|i| self.record.moves[i]);
+            self.game_finished = self.state.is_game_finished();
+            self.needs_redrawing = true;
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state.clone(),
+            last_move: self.last_move,
+            moves: self.record.moves.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.state = snapshot.state;
+        self.last_move = snapshot.last_move;
+        self.record.moves = snapshot.moves;
+        self.rng = snapshot.rng;
+        self.player_moves.clear();
+        self.player_move_sel = None;
+        self.game_finished = self.state.is_game_finished();
+        self.needs_redrawing = true;
     }
 
     fn run_logic(&mut self) {
+        // Undo takes back the player's last move together with the opponent's reply.
+        if self.engine.is_key_pressed(KeyCode::Char('u')) {
+            if let Some(snapshot) = self.undo_stack.pop() {
+                self.redo_stack.push(self.snapshot());
+                self.restore(snapshot);
+            }
+        }
+        if self.engine.is_key_pressed(KeyCode::Char('r')) {
+            if let Some(snapshot) = self.redo_stack.pop() {
+                self.undo_stack.push(self.snapshot());
+                self.restore(snapshot);
+            }
+        }
+
         if !self.game_finished && self.state.is_game_finished() {
             self.game_finished = true;
-            self.last_opponent_move = None;
+            self.last_move = None;
             self.needs_redrawing = true;
         }
 
         if !self.game_finished {
             if self.state.turn == Turn::Opponent {
                 let mov = self
-                    .strategy
-                    .get_next_move(&self.state)
+                    .bot
+                    .get_next_move(&self.state, &mut self.rng)
                     .expect("game not finished");
-                self.last_opponent_move = Some(mov.desc);
+                self.last_move = Some(mov.desc);
+                self.record.push(mov.desc);
                 self.state = mov.state;
                 self.needs_redrawing = true;
             } else if self.state.turn == Turn::Player {
                 if self.player_moves.is_empty() {
-                    self.player_moves = self.state.possible_moves();
+                    self.player_moves = self.state.possible_moves(&self.rules);
                     self.player_moves.sort_unstable_by(cmp_moves_ui_order);
                     assert!(!self.player_moves.is_empty());
                     self.player_move_sel = Some(0);
@@ -101,9 +231,11 @@ impl<'a> Game<'a> {
                     self.needs_redrawing = true;
                 }
                 if self.engine.is_key_pressed(KeyCode::Enter) {
-                    self.state = self.player_moves[self.player_move_sel.unwrap()]
-                        .state
-                        .clone();
+                    self.undo_stack.push(self.snapshot());
+                    self.redo_stack.clear();
+                    let mov = &self.player_moves[self.player_move_sel.unwrap()];
+                    self.record.push(mov.desc);
+                    self.state = mov.state.clone();
                     self.player_moves.clear();
                     self.player_move_sel = None;
                     self.needs_redrawing = true;
@@ -115,10 +247,20 @@ impl<'a> Game<'a> {
     fn redraw(&mut self) {
         self.engine.clear_screen();
 
+        let header = match &self.replay {
+            Some(replay) => format!("Replay: {}/{}", replay.ply, replay.positions.len() - 1),
+            None => format!("Difficulty: {}", self.difficulty),
+        };
+        self.engine.print(0, 0, &header);
         self.print_centered(1, "Opponent");
-        self.print_hand(2, |s| &s.state.opponent_hand);
+        if self.hide_opponent_hand && !self.game_finished {
+            let hidden = "#".repeat(self.state.opponent_hand.len());
+            self.print_centered(2, &hidden);
+        } else {
+            self.print_hand(2, |s| &s.state.opponent_hand);
+        }
         let stack_coords = self.print_hand(5, |s| &s.state.table_stack);
-        self.print_opponent_selector(4, stack_coords);
+        self.print_last_move_selector(4, stack_coords);
         self.print_player_selector_if_take(6, stack_coords);
         let player_coords = self.print_hand(8, |s| &s.state.player_hand);
         self.print_player_selector_if_plays_cards(7, player_coords);
@@ -129,8 +271,14 @@ impl<'a> Game<'a> {
             self.print_strategy_state(11);
         }
         self.engine.print(0, 12, "Controls:");
-        self.engine.print(0, 13, "  Left, Right, Enter: Select");
-        self.engine.print(0, 14, "  Q: Quit");
+        if self.replay.is_some() {
+            self.engine.print(0, 13, "  Left, Right: Step");
+            self.engine.print(0, 14, "  Q: Quit");
+        } else {
+            self.engine.print(0, 13, "  Left, Right, Enter: Select");
+            self.engine.print(0, 14, "  U: Undo, R: Redo, Q: Quit");
+        }
+        self.engine.print(0, 15, &format!("Seed: {}", self.seed));
 
         self.engine.draw();
     }
@@ -142,27 +290,25 @@ impl<'a> Game<'a> {
     }
 
     fn print_hand(&mut self, line: i32, get_hand: fn(&Self) -> &CardsHand) -> (i32, i32) {
-        let mut hand_str = String::new(); // might be optimized
-        if &self.state.table_stack as *const _ == get_hand(self) as *const _ {
+        let mut hand_str = String::new();
+        if self.rules.nine_on_table && std::ptr::eq(&self.state.table_stack, get_hand(self)) {
             // Table stack nine is not taken into account.
-            hand_str.push(CardsHand::IDX_TO_CHAR[CardsHand::CARD_TYPES - 1]);
-        }
-        for i in (0..CardsHand::CARD_TYPES).rev() {
-            let hand = get_hand(self);
-            for _ in 0..hand.cards[i] {
-                hand_str.push(CardsHand::IDX_TO_CHAR[i]);
-            }
+            hand_str.push(CardsHand::IDX_TO_CHAR[CardsHand::NINES_IDX]);
         }
+        hand_str += &get_hand(self).to_string();
         self.print_centered(line, &hand_str)
     }
 
-    fn print_opponent_selector(&mut self, line: i32, coords: (i32, i32)) {
+    fn print_last_move_selector(&mut self, line: i32, coords: (i32, i32)) {
         let end_col = coords.1;
         let pxl = pixel::pxl_fg('v', Color::Red);
-        match self.last_opponent_move {
+        match self.last_move {
             None => (),
+            Some(MoveDescription::PutThree(_)) => {
+                self.engine.line(end_col - 3, line, end_col - 1, line, pxl)
+            }
             Some(MoveDescription::PutAll(i)) => {
-                let cards_put = CardsHand::card_idx_to_cnt(i) as i32;
+                let cards_put = self.rules.card_idx_to_cnt(i) as i32;
                 self.engine
                     .line(end_col - cards_put, line, end_col - 1, line, pxl)
             }
@@ -198,6 +344,10 @@ impl<'a> Game<'a> {
                     let start_col = calc_col(i);
                     self.engine.set_pxl(start_col, line, pxl);
                 }
+                MoveDescription::PutThree(i) => {
+                    let start_col = calc_col(i);
+                    self.engine.line(start_col, line, start_col + 2, line, pxl);
+                }
                 MoveDescription::PutAll(i) => {
                     let start_col = calc_col(i);
                     let end_col = start_col + self.state.player_hand.cards[i] as i32 - 1;
@@ -209,29 +359,25 @@ impl<'a> Game<'a> {
     }
 
     fn print_strategy_state(&mut self, line: i32) {
-        let strategy_state = match self.strategy.get_winning_turn(&self.state) {
-            None => "[S: Draw]",
-            Some(Turn::Player) => "[S: Player]",
-            Some(Turn::Opponent) => "[S: Opponent]",
+        let outcome = self.strategy.get_outcome(&self.state);
+        let strategy_state = match outcome.winner {
+            None => "[S: Draw]".to_string(),
+            Some(Turn::Player) => format!("[S: Player in {}]", outcome.plies),
+            Some(Turn::Opponent) => format!("[S: Opponent in {}]", outcome.plies),
         };
-        self.engine.print(0, line, strategy_state);
+        self.engine.print(0, line, &strategy_state);
     }
 }
 
 fn cmp_moves_ui_order(lhs: &Move, rhs: &Move) -> Ordering {
-    match (&lhs.desc, &rhs.desc) {
-        (MoveDescription::Take, MoveDescription::Take) => Ordering::Equal,
-        (MoveDescription::Take, _) => Ordering::Greater,
-        (_, MoveDescription::Take) => Ordering::Less,
-        (MoveDescription::PutSingle(i), MoveDescription::PutSingle(j))
-        | (MoveDescription::PutAll(i), MoveDescription::PutAll(j)) => j.cmp(i),
-        (MoveDescription::PutSingle(i), MoveDescription::PutAll(j)) => match j.cmp(i) {
-            Ordering::Equal => Ordering::Less,
-            ord => ord,
-        },
-        (MoveDescription::PutAll(i), MoveDescription::PutSingle(j)) => match j.cmp(i) {
-            Ordering::Equal => Ordering::Greater,
-            ord => ord,
-        },
+    // Weaker cards first, fewer cards of the same rank first, taking last.
+    fn key(desc: &MoveDescription) -> (bool, Reverse<usize>, u8) {
+        match *desc {
+            MoveDescription::PutSingle(i) => (false, Reverse(i), 0),
+            MoveDescription::PutThree(i) => (false, Reverse(i), 1),
+            MoveDescription::PutAll(i) => (false, Reverse(i), 2),
+            MoveDescription::Take => (true, Reverse(0), 0),
+        }
     }
+    key(&lhs.desc).cmp(&key(&rhs.desc))
 }
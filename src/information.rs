@@ -0,0 +1,184 @@
+use crate::{
+    multi::{MultiState, MultiStrategy},
+    rules::Rules,
+    state::{CardsHand, MoveDescription},
+};
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+/// What one player knows about the game: their own hand, the table stack,
+/// how many cards the others hold and which of them are known
+/// (because they were taken from the table and not put back since).
+///
+/// With two players all cards are in play, so the other hand can always be inferred exactly;
+/// the unknown part matters with more players, where it is split between several hands.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct InformationSet {
+    /// Index of the player this is the knowledge of.
+    pub me: usize,
+    pub own_hand: CardsHand,
+    pub table_stack: CardsHand,
+    /// Index of the player to move.
+    pub turn: usize,
+    /// Number of cards held by each player.
+    pub cards_cnts: Vec<usize>,
+    /// Cards each player is known to hold.
+    pub known_cards: Vec<CardsHand>,
+}
+
+impl InformationSet {
+    /// Knowledge of player `me` right after the deal.
+    pub fn new(state: &MultiState, me: usize) -> Self {
+        let mut known_cards = vec![CardsHand::EMPTY; state.players_cnt()];
+        known_cards[me] = state.hands[me].clone();
+        Self {
+            me,
+            own_hand: state.hands[me].clone(),
+            table_stack: state.table_stack.clone(),
+            turn: state.turn,
+            cards_cnts: state.hands.iter().map(CardsHand::len).collect(),
+            known_cards,
+        }
+    }
+
+    /// Updates the knowledge with a move made by the player to move.
+    pub fn observe(&mut self, desc: MoveDescription, rules: &Rules) -> Result<(), &'static str> {
+        let p = self.turn;
+        // Hand of the mover with all cards they might hold.
+        let mover_hand = if p == self.me {
+            self.own_hand.clone()
+        } else {
+            let mut hand = self.unknown_cards(rules);
+            for i in 0..CardsHand::CARD_TYPES {
+                hand.cards[i] += self.known_cards[p].cards[i];
+            }
+            hand
+        };
+        let (_, _, table_after) = mover_hand
+            .moves_onto(&self.table_stack, rules)
+            .into_iter()
+            .find(|(d, _, _)| *d == desc)
+            .ok_or("Illegal move")?;
+
+        let (mut known, mut cards_cnt) = (self.known_cards[p].clone(), self.cards_cnts[p]);
+        for i in 0..CardsHand::CARD_TYPES {
+            if table_after.cards[i] > self.table_stack.cards[i] {
+                let put = table_after.cards[i] - self.table_stack.cards[i];
+                cards_cnt = cards_cnt
+                    .checked_sub(usize::from(put))
+                    .ok_or("Illegal move")?;
+                known.cards[i] = known.cards[i].saturating_sub(put);
+            } else {
+                let taken = self.table_stack.cards[i] - table_after.cards[i];
+                cards_cnt += usize::from(taken);
+                known.cards[i] += taken;
+            }
+        }
+
+        if p == self.me {
+            self.own_hand = known.clone();
+        }
+        self.known_cards[p] = known;
+        self.cards_cnts[p] = cards_cnt;
+        self.table_stack = table_after;
+        self.turn = self.next_turn();
+        Ok(())
+    }
+
+    /// Cards not in own hand, on the table nor known to be held by anyone.
+    pub fn unknown_cards(&self, rules: &Rules) -> CardsHand {
+        let mut unknown = CardsHand::EMPTY;
+        for i in 0..CardsHand::CARD_TYPES {
+            let seen =
+                self.table_stack.cards[i] + self.known_cards.iter().map(|h| h.cards[i]).sum::<u8>();
+            unknown.cards[i] = rules.card_idx_to_cnt(i) as u8 - seen;
+        }
+        unknown
+    }
+
+    pub fn is_consistent(&self, state: &MultiState) -> bool {
+        state.turn == self.turn
+            && state.table_stack == self.table_stack
+            && state.hands[self.me] == self.own_hand
+            && state.hands.len() == self.cards_cnts.len()
+            && state.hands.iter().enumerate().all(|(p, h)| {
+                h.len() == self.cards_cnts[p]
+                    && (0..CardsHand::CARD_TYPES)
+                        .all(|i| h.cards[i] >= self.known_cards[p].cards[i])
+            })
+    }
+
+    /// Samples a state consistent with the knowledge, distributing unknown cards uniformly.
+    pub fn determinize(&self, rules: &Rules, rng: &mut impl Rng) -> MultiState {
+        let mut pool = vec![];
+        let unknown = self.unknown_cards(rules);
+        for i in 0..CardsHand::CARD_TYPES {
+            pool.extend(std::iter::repeat_n(i, usize::from(unknown.cards[i])));
+        }
+        pool.shuffle(rng);
+
+        let mut hands = self.known_cards.clone();
+        for (p, hand) in hands.iter_mut().enumerate() {
+            let missing = self.cards_cnts[p] - hand.len();
+            for i in pool.drain(pool.len() - missing..) {
+                hand.cards[i] += 1;
+            }
+        }
+        assert!(pool.is_empty(), "Inconsistent information set");
+
+        MultiState {
+            hands,
+            table_stack: self.table_stack.clone(),
+            turn: self.turn,
+        }
+    }
+
+    fn next_turn(&self) -> usize {
+        let players_cnt = self.cards_cnts.len();
+        (1..players_cnt)
+            .map(|i| (self.turn + i) % players_cnt)
+            .find(|&i| self.cards_cnts[i] > 0)
+            .unwrap_or(self.turn)
+    }
+}
+
+/// Strategy playing only on the knowledge of the player to move.
+pub trait InformedStrategy {
+    fn get_next_move(
+        &self,
+        info: &InformationSet,
+        rng: &mut dyn RngCore,
+    ) -> Option<MoveDescription>;
+}
+
+/// Samples states consistent with the knowledge, lets a perfect-information strategy
+/// choose a move in each of them and plays the most frequently chosen one.
+pub struct Determinized<S> {
+    pub rules: Rules,
+    pub samples: usize,
+    pub strategy: S,
+}
+
+impl<S: MultiStrategy> InformedStrategy for Determinized<S> {
+    fn get_next_move(
+        &self,
+        info: &InformationSet,
+        mut rng: &mut dyn RngCore,
+    ) -> Option<MoveDescription> {
+        let mut votes: Vec<(MoveDescription, usize)> = vec![];
+        for _ in 0..self.samples.max(1) {
+            let state = info.determinize(&self.rules, &mut rng);
+            let Some(mov) = self.strategy.get_next_move(&state, rng) else {
+                continue;
+            };
+            match votes.iter_mut().find(|(d, _)| *d == mov.desc) {
+                Some((_, cnt)) => *cnt += 1,
+                None => votes.push((mov.desc, 1)),
+            }
+        }
+
+        votes
+            .into_iter()
+            .max_by_key(|(_, cnt)| *cnt)
+            .map(|(d, _)| d)
+    }
+}
@@ -0,0 +1,21 @@
+//! Engine and solver for the card game Pan.
+//!
+//! The console game lives in the `pan` binary; this library exposes the game state
+//! ([`state`], or [`suited`] when suits matter) played under some rule variant ([`rules`])
+//! and the strategies playing it ([`strategy`]), including the solver cache, so they can be
+//! used and tested without a terminal. Games of more than two players are in [`multi`],
+//! play with hidden hands in [`information`], records of played games in [`record`].
+
+// mod playground;
+pub mod information;
+pub mod multi;
+pub mod record;
+pub mod rules;
+pub mod state;
+pub mod strategy;
+pub mod suited;
+
+pub use record::GameRecord;
+pub use rules::Rules;
+pub use state::{CardsHand, Move, MoveDescription, State, Turn, VerboseState};
+pub use strategy::{Difficulty, Mcts, Minimax, Optimal, OptimalCache, Outcome, Random, Strategy};
@@ -1,33 +1,289 @@
-// TODO: maybe lib.rs?
-
+mod cli;
 mod game;
-// mod playground;
-mod state;
-mod strategy;
+mod multi_game;
 
-static CACHE_PATH: &str = "pan_cache.bin";
+use cli::{CacheCommand, Command, Options, Position};
+use pan::{
+    record::GameRecord,
+    rules::Rules,
+    state::{Turn, VerboseState},
+    strategy::{play_out, Difficulty, Optimal, OptimalCache, Outcome},
+};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process,
+};
 
-fn main() {
-    let state = state::VerboseState::random();
-    let mut cache = strategy::OptimalCache::new();
+/// Simulated games not finished within this number of plies are draws.
+const SIMULATION_MAX_PLIES: usize = 1000;
 
-    println!("Trying to load cache if present.");
-    if let Err(err) = cache.load_from_disk(CACHE_PATH) {
-        eprintln!("Error while loading cache: {}", err)
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
     };
 
-    let cache_len = cache.len();
+    if let Err(err) = run(&options) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let rules = options.rules;
+    // Every game is seeded, so it can be played again.
+    let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+    match &options.command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Multi { seats } => {
+            let seats = multi_game::Seat::parse_seats(seats, &rules)?;
+            multi_game::MultiGame::new(seats, rules, seed).run();
+        }
+        Command::Cache(cache_command) => run_cache_command(cache_command, options)?,
+        Command::Solve => {
+            let mut cache = open_cache(options.rules, options);
+            println!("Solving all states, this may take a few minutes.");
+            Optimal::solve_all(&mut cache);
+            cache.save_to_disk(options.cache_path(&rules))?;
+        }
+        Command::Analyze => {
+            println!("Seed: {}", seed);
+            let state = start_state(options, seed)?;
+            let mut cache = open_cache(options.rules, options);
+            let cache_len = cache.len();
+            let strategy = Optimal::new_with_mut_cache(&state, &mut cache);
+            print_analysis(&state, &strategy);
+            save_cache_if_grown(&mut cache, cache_len, options);
+        }
+        Command::Simulate => {
+            let mut cache = open_cache(options.rules, options);
+            let cache_len = cache.len();
+            simulate(&mut cache, seed, options)?;
+            save_cache_if_grown(&mut cache, cache_len, options);
+        }
+        Command::Play => {
+            let state = start_state(options, seed)?;
+            let mut cache = open_cache(options.rules, options);
+            let cache_len = cache.len();
+            println!("If cache is not built up, calculating strategy may take a few minutes.");
+            let difficulty = options.difficulty.unwrap_or_else(ask_for_difficulty);
+            println!("Seed: {}", seed);
+            println!("Position: {}", state);
+            let record = game::Game::new(
+                state.clone(),
+                &mut cache,
+                difficulty,
+                options.hide_opponent_hand,
+                seed,
+            )
+            .run();
+            println!("Seed: {}", seed);
+            println!("Position: {}", state);
+            match record.append_to_file(&options.record_path) {
+                Ok(()) => println!("Game recorded to {}", options.record_path.display()),
+                Err(err) => eprintln!("Error while recording game: {}", err),
+            }
+            save_cache_if_grown(&mut cache, cache_len, options);
+        }
+        Command::Replay(game) => {
+            let path = &options.record_path;
+            let contents =
+                fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let mut records = GameRecord::parse_all(&contents)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            let idx = game.unwrap_or(records.len());
+            if idx == 0 || idx > records.len() {
+                return Err(format!(
+                    "{}: no game {}, there are {}",
+                    path.display(),
+                    idx,
+                    records.len()
+                ));
+            }
+            let record = records.swap_remove(idx - 1);
+            // The cache of the recorded rules, which may differ from the given ones.
+            let mut cache = open_cache(record.rules, options);
+            let cache_len = cache.len();
+            game::Game::replay(record, &mut cache)?.run();
+            save_cache_if_grown(&mut cache, cache_len, options);
+        }
+    }
+    Ok(())
+}
+
+fn start_state(options: &Options, seed: u64) -> Result<VerboseState, String> {
+    let rules = &options.rules;
+    let mut state = match &options.position {
+        Position::Initial => VerboseState::initial(rules),
+        Position::Random => VerboseState::deal_with_rng(rules, &mut StdRng::seed_from_u64(seed)),
+        Position::Notation(notation) => VerboseState::parse(notation, rules)
+            .map_err(|err| format!("Invalid position '{}': {}", notation, err))?,
+        Position::File(path) => fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|notation| VerboseState::parse(&notation, rules))
+            .map_err(|err| format!("{}: {}", path.display(), err))?,
+    };
+    if let Some(first) = options.first {
+        state.turn = first;
+        // E.g. the side without the nine of hearts cannot open the game.
+        state
+            .validate(rules)
+            .map_err(|err| format!("Invalid position after --first: {}", err))?;
+    }
+    Ok(state)
+}
 
-    println!("If cache is not built up, calculating strategy may take a few minutes.");
-    game::Game::new(state, &mut cache).run();
+fn open_cache(rules: Rules, options: &Options) -> OptimalCache {
+    println!("Trying to load cache if present.");
+    let mut cache = OptimalCache::open(rules, options.cache_path(&rules))
+        .and_then(|cache| {
+            if cache.has_plies() {
+                Ok(cache)
+            } else {
+                Err("Cache without plies to the end, which optimal play needs.".to_string())
+            }
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Error while loading cache: {}", err);
+            OptimalCache::new(rules)
+        });
+    if let Some(threads) = options.threads {
+        cache.set_threads(threads);
+    }
+    cache
+}
 
+fn save_cache_if_grown(cache: &mut OptimalCache, cache_len: usize, options: &Options) {
     if cache.len() != cache_len {
-        // In theory might be saved right after calculating the strategy, but it does not matter much.
-        if let Err(err) = cache.save_to_disk(CACHE_PATH) {
+        let path = options.cache_path(cache.rules());
+        if let Err(err) = cache.merge_to_disk(path) {
             eprintln!("Error while saving cache: {}", err);
         }
     }
 }
 
+fn describe_outcome(outcome: Outcome) -> String {
+    match outcome.winner {
+        None => "draw".to_string(),
+        Some(t) => format!("{:?} wins in {} plies", t, outcome.plies),
+    }
+}
+
+fn print_analysis(state: &VerboseState, strategy: &Optimal) {
+    println!("Position: {}", state);
+    println!("Opponent: {}", state.opponent_hand);
+    println!("Table: {}", state.table_stack);
+    println!("Player: {}", state.player_hand);
+    println!("{:?} to move", state.turn);
+    println!("Outcome: {}", describe_outcome(strategy.get_outcome(state)));
+    for m in state.possible_moves(strategy.rules()) {
+        println!(
+            "  {} ({}): {}",
+            m.desc,
+            m.desc.describe(),
+            describe_outcome(strategy.get_outcome(&m.state))
+        );
+    }
+}
+
+fn simulate(cache: &mut OptimalCache, seed: u64, options: &Options) -> Result<(), String> {
+    let opponent_difficulty = options.difficulty.unwrap_or_default();
+    let (mut player_wins, mut opponent_wins, mut draws) = (0, 0, 0);
+    println!("Seeds from: {}", seed);
+    for game in 0..options.games {
+        // Consecutive seeds, so any game can be played again.
+        let seed = seed.wrapping_add(game as u64);
+        let state = start_state(options, seed)?;
+        let optimal = Optimal::new_with_mut_cache(&state, cache);
+        let player = options.player_difficulty.strategy(optimal);
+        let opponent = opponent_difficulty.strategy(optimal);
+        let mut rng = StdRng::seed_from_u64(seed);
+        match play_out(&state, &*player, &*opponent, SIMULATION_MAX_PLIES, &mut rng) {
+            Some(Turn::Player) => player_wins += 1,
+            Some(Turn::Opponent) => opponent_wins += 1,
+            None => draws += 1,
+        }
+    }
+    println!(
+        "Player ({}): {} wins, opponent ({}): {} wins, {} draws",
+        options.player_difficulty, player_wins, opponent_difficulty, opponent_wins, draws
+    );
+    Ok(())
+}
+
+fn run_cache_command(command: &CacheCommand, options: &Options) -> Result<(), String> {
+    let load = |path: &Path| {
+        let mut cache = OptimalCache::new(options.rules);
+        cache
+            .load_from_disk(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok::<_, String>(cache)
+    };
+    let path_or_default = |path: &Option<_>| {
+        path.clone()
+            .unwrap_or_else(|| options.cache_path(&options.rules))
+    };
+
+    match command {
+        CacheCommand::Stats(path) => println!("{}", load(&path_or_default(path))?.stats()),
+        CacheCommand::Verify(path) => verify_cache(&load(&path_or_default(path))?)?,
+        CacheCommand::Diff(left, right) => println!("{}", load(left)?.diff(&load(right)?)?),
+        CacheCommand::Merge { output, inputs } => {
+            let mut cache = OptimalCache::new(options.rules);
+            for input in inputs {
+                let added = cache
+                    .merge(&load(input)?)
+                    .map_err(|err| format!("{}: {}", input.display(), err))?;
+                println!("{}: {} states added", input.display(), added);
+            }
+            if options.verdicts_only {
+                cache.drop_plies();
+            }
+            cache.merge_to_disk(output)?;
+            println!("{}: {} states", output.display(), cache.len());
+        }
+    }
+    Ok(())
+}
+
+fn verify_cache(cache: &OptimalCache) -> Result<(), String> {
+    let inconsistencies = cache.verify();
+    for inconsistency in &inconsistencies {
+        println!("{}", inconsistency);
+    }
+    match inconsistencies.len() {
+        0 => {
+            println!("All {} states consistent.", cache.len());
+            Ok(())
+        }
+        cnt => Err(format!("{} inconsistent states", cnt)),
+    }
+}
+
+fn ask_for_difficulty() -> Difficulty {
+    loop {
+        print!(
+            "Difficulty (random, greedy, epsilon[:P], optimal) [{}]: ",
+            Difficulty::default()
+        );
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            return Difficulty::default();
+        }
+        match line.trim().parse() {
+            Ok(difficulty) => return difficulty,
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
+
 // TODO:
 // - displaying strategy state does not work (or maybe strategy does not work?)
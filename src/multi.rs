@@ -0,0 +1,233 @@
+use crate::{
+    rules::Rules,
+    state::{CardsHand, MoveDescription, Turn, VerboseState},
+    strategy::{Optimal, Strategy},
+    suited::{Card, SuitedHand},
+};
+use rand::{seq::SliceRandom, thread_rng, Rng, RngCore};
+
+/// State of a game of two or more players.
+///
+/// Players who got rid of all their cards leave the game; the last player holding cards loses.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct MultiState {
+    pub hands: Vec<CardsHand>,
+    pub table_stack: CardsHand,
+    /// Index of the player to move.
+    pub turn: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct MultiMove {
+    pub state: MultiState,
+    pub desc: MoveDescription,
+}
+
+impl MultiState {
+    pub const MIN_PLAYERS: usize = 2;
+    pub const MAX_PLAYERS: usize = 4;
+
+    /// Shuffles the deck and deals it between players.
+    /// With `Rules::nine_of_hearts_starts`, the holder of the nine of hearts moves first.
+    pub fn deal(players_cnt: usize, rules: &Rules) -> Self {
+        Self::deal_with_rng(players_cnt, rules, &mut thread_rng())
+    }
+
+    /// `deal` with given source of randomness.
+    pub fn deal_with_rng(players_cnt: usize, rules: &Rules, rng: &mut impl Rng) -> Self {
+        assert!(
+            (Self::MIN_PLAYERS..=Self::MAX_PLAYERS).contains(&players_cnt),
+            "Invalid number of players"
+        );
+
+        let mut deck = SuitedHand::full_deck().iter().collect::<Vec<_>>();
+        if rules.nine_on_table {
+            deck.retain(|&c| c != Card::NINE_OF_HEARTS);
+        }
+        deck.shuffle(rng);
+
+        let mut hands = vec![SuitedHand::EMPTY; players_cnt];
+        for (i, c) in deck.into_iter().enumerate() {
+            hands[i % players_cnt].insert(c);
+        }
+        let turn = if rules.is_bottom_nine_fixed() {
+            hands
+                .iter()
+                .position(|h| h.contains(Card::NINE_OF_HEARTS))
+                .unwrap()
+        } else {
+            0
+        };
+
+        Self {
+            hands: hands.iter().map(SuitedHand::to_cards_hand).collect(),
+            table_stack: CardsHand::EMPTY,
+            turn,
+        }
+    }
+
+    pub fn players_cnt(&self) -> usize {
+        self.hands.len()
+    }
+
+    pub fn possible_moves(&self, rules: &Rules) -> Vec<MultiMove> {
+        if self.is_game_finished() {
+            return vec![];
+        }
+
+        self.hands[self.turn]
+            .moves_onto(&self.table_stack, rules)
+            .into_iter()
+            .map(|(desc, hand, table_stack)| {
+                let mut s = self.clone();
+                s.hands[s.turn] = hand;
+                s.table_stack = table_stack;
+                s.turn = s.next_turn();
+                MultiMove { state: s, desc }
+            })
+            .collect()
+    }
+
+    pub fn is_game_finished(&self) -> bool {
+        self.hands.iter().filter(|h| !h.is_empty()).count() <= 1
+    }
+
+    /// The last player holding cards, once the game is finished.
+    pub fn loser(&self) -> Option<usize> {
+        if self.is_game_finished() {
+            self.hands.iter().position(|h| !h.is_empty())
+        } else {
+            None
+        }
+    }
+
+    /// Next player still holding cards, or the current player if there is no such.
+    fn next_turn(&self) -> usize {
+        (1..self.players_cnt())
+            .map(|i| (self.turn + i) % self.players_cnt())
+            .find(|&i| !self.hands[i].is_empty())
+            .unwrap_or(self.turn)
+    }
+}
+
+/// Player is the first one, opponent the second one.
+impl From<&VerboseState> for MultiState {
+    fn from(vs: &VerboseState) -> Self {
+        Self {
+            hands: vec![vs.player_hand.clone(), vs.opponent_hand.clone()],
+            table_stack: vs.table_stack.clone(),
+            turn: match vs.turn {
+                Turn::Player => 0,
+                Turn::Opponent => 1,
+            },
+        }
+    }
+}
+
+impl MultiState {
+    /// Only for two players.
+    pub fn to_verbose(&self) -> Option<VerboseState> {
+        match self.hands.as_slice() {
+            [player_hand, opponent_hand] => Some(VerboseState {
+                player_hand: player_hand.clone(),
+                opponent_hand: opponent_hand.clone(),
+                table_stack: self.table_stack.clone(),
+                turn: if self.turn == 0 {
+                    Turn::Player
+                } else {
+                    Turn::Opponent
+                },
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub trait MultiStrategy {
+    fn get_next_move(&self, state: &MultiState, rng: &mut dyn RngCore) -> Option<MultiMove>;
+}
+
+pub struct MultiRandom {
+    pub rules: Rules,
+}
+
+impl MultiStrategy for MultiRandom {
+    fn get_next_move(&self, state: &MultiState, rng: &mut dyn RngCore) -> Option<MultiMove> {
+        state
+            .possible_moves(&self.rules)
+            .as_slice()
+            .choose(rng)
+            .cloned()
+    }
+}
+
+/// Only for two players.
+impl<'a> MultiStrategy for Optimal<'a> {
+    fn get_next_move(&self, state: &MultiState, rng: &mut dyn RngCore) -> Option<MultiMove> {
+        let mov = Strategy::get_next_move(self, &state.to_verbose()?, rng)?;
+        Some(MultiMove {
+            state: MultiState::from(&mov.state),
+            desc: mov.desc,
+        })
+    }
+}
+
+/// Depth-limited max^n search: every player maximizes their own score,
+/// which is minus the number of cards held, and a big penalty for losing.
+pub struct MaxN {
+    pub rules: Rules,
+    /// In plies.
+    pub depth: usize,
+}
+
+impl MaxN {
+    const LOSS_SCORE: i32 = -1000;
+
+    fn evaluate(state: &MultiState) -> Vec<i32> {
+        let loser = state.loser();
+        state
+            .hands
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                if loser == Some(i) {
+                    Self::LOSS_SCORE
+                } else {
+                    -(h.len() as i32)
+                }
+            })
+            .collect()
+    }
+
+    fn search(&self, state: &MultiState, depth: usize) -> Vec<i32> {
+        if depth == 0 || state.is_game_finished() {
+            return Self::evaluate(state);
+        }
+
+        state
+            .possible_moves(&self.rules)
+            .iter()
+            .map(|m| self.search(&m.state, depth - 1))
+            .max_by_key(|scores| scores[state.turn])
+            .expect("game not finished")
+    }
+}
+
+impl MultiStrategy for MaxN {
+    fn get_next_move(&self, state: &MultiState, rng: &mut dyn RngCore) -> Option<MultiMove> {
+        let depth = self.depth.saturating_sub(1);
+        let scored = state
+            .possible_moves(&self.rules)
+            .into_iter()
+            .map(|m| (self.search(&m.state, depth)[state.turn], m))
+            .collect::<Vec<_>>();
+        let best = scored.iter().map(|(score, _)| *score).max()?;
+
+        let best_moves = scored
+            .into_iter()
+            .filter(|(score, _)| *score == best)
+            .map(|(_, m)| m)
+            .collect::<Vec<_>>();
+        best_moves.choose(rng).cloned()
+    }
+}
@@ -0,0 +1,157 @@
+use pan::{
+    information::{Determinized, InformationSet, InformedStrategy},
+    multi::{MaxN, MultiState},
+    rules::Rules,
+    state::{CardsHand, MoveDescription},
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::io::{self, BufRead, Write};
+
+pub enum Seat {
+    Human,
+    /// Knows only own cards and what it observed.
+    Bot(Box<dyn InformedStrategy>),
+}
+
+impl Seat {
+    /// Parses seats given as a string of `h` (human) and `b` (bot) letters, e.g. `hbbb`.
+    pub fn parse_seats(s: &str, rules: &Rules) -> Result<Vec<Self>, String> {
+        let seats = s
+            .chars()
+            .map(|c| match c {
+                'h' => Ok(Seat::Human),
+                'b' => Ok(Seat::Bot(Box::new(Determinized {
+                    rules: *rules,
+                    samples: MultiGame::BOT_SAMPLES,
+                    strategy: MaxN {
+                        rules: *rules,
+                        depth: MultiGame::BOT_DEPTH,
+                    },
+                }))),
+                _ => Err(format!("Unknown seat '{}', expected 'h' or 'b'", c)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !(MultiState::MIN_PLAYERS..=MultiState::MAX_PLAYERS).contains(&seats.len()) {
+            return Err(format!(
+                "Expected {} to {} seats",
+                MultiState::MIN_PLAYERS,
+                MultiState::MAX_PLAYERS
+            ));
+        }
+        Ok(seats)
+    }
+}
+
+/// Text-mode game of several humans (sharing the terminal) and bots.
+pub struct MultiGame {
+    state: MultiState,
+    rules: Rules,
+    seats: Vec<Seat>,
+    /// Knowledge of each seat.
+    infos: Vec<InformationSet>,
+    seed: u64,
+    /// Of the deal and the bots.
+    rng: StdRng,
+}
+
+impl MultiGame {
+    const BOT_DEPTH: usize = 3;
+    const BOT_SAMPLES: usize = 8;
+
+    pub fn new(seats: Vec<Seat>, rules: Rules, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let state = MultiState::deal_with_rng(seats.len(), &rules, &mut rng);
+        let infos = (0..seats.len())
+            .map(|p| InformationSet::new(&state, p))
+            .collect();
+        Self {
+            state,
+            rules,
+            seats,
+            infos,
+            seed,
+            rng,
+        }
+    }
+
+    pub fn run(mut self) {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        println!("Seed: {}", self.seed);
+
+        while !self.state.is_game_finished() {
+            self.print_table();
+            let turn = self.state.turn;
+            let moves = self.state.possible_moves(&self.rules);
+
+            let mov = match &self.seats[turn] {
+                Seat::Bot(strategy) => {
+                    let desc = strategy
+                        .get_next_move(&self.infos[turn], &mut self.rng)
+                        .expect("game not finished");
+                    moves
+                        .into_iter()
+                        .find(|m| m.desc == desc)
+                        .expect("bot plays legal moves")
+                }
+                Seat::Human => {
+                    println!("Your hand: {}", self.state.hands[turn]);
+                    for (i, m) in moves.iter().enumerate() {
+                        println!("  {} ({}): {}", i + 1, m.desc, m.desc.describe());
+                    }
+                    loop {
+                        print!("Player {}, select move (number or notation): ", turn + 1);
+                        io::stdout().flush().unwrap();
+                        let line = match lines.next() {
+                            Some(Ok(line)) => line,
+                            _ => return,
+                        };
+                        let line = line.trim();
+                        if let Ok(i) = line.parse::<usize>() {
+                            match moves.get(i.wrapping_sub(1)) {
+                                Some(m) => break m.clone(),
+                                None => println!("Expected a number from 1 to {}.", moves.len()),
+                            }
+                            continue;
+                        }
+                        match line.parse::<MoveDescription>() {
+                            Ok(desc) => match moves.iter().find(|m| m.desc == desc) {
+                                Some(m) => break m.clone(),
+                                None => println!("Move {} is not possible.", desc),
+                            },
+                            Err(err) => println!("{}", err),
+                        }
+                    }
+                }
+            };
+
+            println!("Player {}: {}", turn + 1, mov.desc.describe());
+            for info in &mut self.infos {
+                info.observe(mov.desc, &self.rules)
+                    .expect("information set follows the game");
+            }
+            self.state = mov.state;
+        }
+
+        self.print_table();
+        let loser = self.state.loser().expect("game finished");
+        println!("Game over, player {} lost.", loser + 1);
+    }
+
+    fn print_table(&self) {
+        println!();
+        for (i, hand) in self.state.hands.iter().enumerate() {
+            let kind = match self.seats[i] {
+                Seat::Human => "human",
+                Seat::Bot(_) => "bot",
+            };
+            println!("Player {} ({}): {} cards", i + 1, kind, hand.len());
+        }
+        let mut table = self.state.table_stack.to_string();
+        if self.rules.nine_on_table {
+            // Table stack nine is not taken into account.
+            table.insert(0, CardsHand::IDX_TO_CHAR[CardsHand::NINES_IDX]);
+        }
+        println!("Table: {}", table);
+    }
+}
@@ -1,24 +1,23 @@
 use crate::{
+    rules::Rules,
     state::{State, VerboseState},
     strategy::Optimal,
 };
-use std::{
-    collections::HashSet,
-    convert::{TryFrom, TryInto},
-};
+use std::collections::HashSet;
 
 pub fn foo() {
     fn visit(
         start_state: State,
-        next_states: fn(&VerboseState) -> Vec<VerboseState>,
+        rules: &Rules,
+        next_states: fn(&VerboseState, &Rules) -> Vec<VerboseState>,
     ) -> HashSet<State> {
         let mut q = vec![start_state];
         let mut visited = HashSet::new();
 
         while let Some(s) = q.pop() {
-            let following_states = next_states(&VerboseState::from(s));
+            let following_states = next_states(&VerboseState::from_state(s, rules), rules);
             for s in following_states {
-                let s = State::try_from(s).unwrap();
+                let s = State::from_verbose(&s, rules).unwrap();
                 if visited.contains(&s) {
                     continue;
                 }
@@ -30,9 +29,10 @@ pub fn foo() {
         visited
     }
 
-    let start_state: State = VerboseState::random().try_into().unwrap();
-    let visited_f = visit(start_state, VerboseState::following_states);
-    let visited_p = visit(start_state, VerboseState::preceding_states);
+    let rules = Rules::default();
+    let start_state = State::from_verbose(&VerboseState::random(&rules), &rules).unwrap();
+    let visited_f = visit(start_state, &rules, VerboseState::following_states);
+    let visited_p = visit(start_state, &rules, VerboseState::preceding_states);
     let intersection = visited_f.intersection(&visited_p).collect::<Vec<_>>();
 
     // Visited states: 15162474, 15137488, 15137466.
@@ -45,12 +45,12 @@ pub fn foo() {
     );
 
     for s in visited_p.difference(&visited_f) {
-        println!("{:?}", VerboseState::from(*s));
+        println!("{:?}", VerboseState::from_state(*s, &rules));
     }
 }
 
 pub fn bar() {
-    let _start_state = VerboseState::random();
+    let _start_state = VerboseState::random(&Rules::default());
     // let _optimal = Optimal::new(&start_state);
 }
 
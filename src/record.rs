@@ -0,0 +1,296 @@
+//! Records of played games in a PGN-like text format: tags in brackets, a blank line and
+//! the moves in the notation of `MoveDescription`, e.g.
+//!
+//! ```
+//! # let record: pan::GameRecord = r#"
+//! [Started "1760781234"]
+//! [Ended "1760781301"]
+//! [Seed "42"]
+//! [Opponent "optimal"]
+//! [Take "3"]
+//! [PutThree "false"]
+//! [NineOnTable "true"]
+//! [NineOfHeartsStarts "false"]
+//! [Position "9911JJQQKKAA|-|911JJQQKKAA p"]
+//! [Result "*"]
+//!
+//! 9 9 1 T
+//! # "#.parse().unwrap();
+//! # assert_eq!(record.moves.len(), 4);
+//! ```
+//!
+//! Times are Unix timestamps in seconds. Result is `*` for games left unfinished.
+//! Files may hold many games, one after another, each starting with its `Started` tag.
+
+use crate::{
+    rules::Rules,
+    state::{MoveDescription, Turn, VerboseState},
+    strategy::Difficulty,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub rules: Rules,
+    pub opponent: Difficulty,
+    pub seed: u64,
+    pub start: VerboseState,
+    pub moves: Vec<MoveDescription>,
+    /// `None` while the game is not finished.
+    pub winner: Option<Turn>,
+    pub started: u64,
+    /// When the game was finished or left.
+    pub ended: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl GameRecord {
+    /// Record of a game starting now.
+    pub fn new(rules: Rules, opponent: Difficulty, seed: u64, start: VerboseState) -> Self {
+        Self {
+            rules,
+            opponent,
+            seed,
+            start,
+            moves: vec![],
+            winner: None,
+            started: now(),
+            ended: None,
+        }
+    }
+
+    pub fn push(&mut self, desc: MoveDescription) {
+        self.moves.push(desc);
+    }
+
+    pub fn end(&mut self, winner: Option<Turn>) {
+        self.winner = winner;
+        self.ended = Some(now());
+    }
+
+    /// The start position followed by the position after every move.
+    pub fn positions(&self) -> Result<Vec<VerboseState>, String> {
+        let mut positions = vec![self.start.clone()];
+        for (ply, &desc) in self.moves.iter().enumerate() {
+            let next = positions[ply]
+                .apply_move(desc, &self.rules)
+                .map_err(|err| format!("Move {} ({}): {}", ply + 1, desc, err))?;
+            positions.push(next);
+        }
+        Ok(positions)
+    }
+
+    /// Parses all games of a file's contents, each starting with its `Started` tag.
+    pub fn parse_all(s: &str) -> Result<Vec<Self>, String> {
+        let mut games = vec![];
+        let mut game = String::new();
+        for line in s.lines() {
+            // Games without moves have no move line to end them.
+            if line.trim_start().starts_with("[Started ") && !game.trim().is_empty() {
+                games.push(game.parse()?);
+                game.clear();
+            }
+            game += line;
+            game.push('\n');
+        }
+        if !game.trim().is_empty() {
+            games.push(game.parse()?);
+        }
+        Ok(games)
+    }
+
+    /// Appends the record to the file, creating it if needed.
+    pub fn append_to_file(&self, path: &Path) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        writeln!(file, "{}\n", self).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Started \"{}\"]", self.started)?;
+        if let Some(ended) = self.ended {
+            writeln!(f, "[Ended \"{}\"]", ended)?;
+        }
+        writeln!(f, "[Seed \"{}\"]", self.seed)?;
+        writeln!(f, "[Opponent \"{}\"]", self.opponent)?;
+        writeln!(
+            f,
+            "[Take \"{}\"]",
+            self.rules.cards_cnt_when_taking_from_stack
+        )?;
+        writeln!(f, "[PutThree \"{}\"]", self.rules.allow_put_three)?;
+        writeln!(f, "[NineOnTable \"{}\"]", self.rules.nine_on_table)?;
+        writeln!(
+            f,
+            "[NineOfHeartsStarts \"{}\"]",
+            self.rules.nine_of_hearts_starts
+        )?;
+        writeln!(f, "[Position \"{}\"]", self.start)?;
+        let result = match self.winner {
+            Some(Turn::Player) => "player",
+            Some(Turn::Opponent) => "opponent",
+            None => "*",
+        };
+        writeln!(f, "[Result \"{}\"]", result)?;
+        writeln!(f)?;
+        let moves = self.moves.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", moves.join(" "))
+    }
+}
+
+/// Inverse of `Display` for a single game, checking all moves are legal.
+impl FromStr for GameRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tags = HashMap::new();
+        let mut moves = vec![];
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                let (name, value) = line
+                    .strip_prefix('[')
+                    .and_then(|l| l.strip_suffix(']'))
+                    .and_then(|l| l.split_once(' '))
+                    .and_then(|(name, value)| {
+                        Some((name, value.strip_prefix('"')?.strip_suffix('"')?))
+                    })
+                    .ok_or_else(|| format!("Line {}: invalid tag '{}'", line_idx + 1, line))?;
+                tags.insert(name, value);
+            } else {
+                for m in line.split_whitespace() {
+                    moves.push(
+                        m.parse()
+                            .map_err(|err| format!("Line {}: {}", line_idx + 1, err))?,
+                    );
+                }
+            }
+        }
+
+        let tag = |name: &str| {
+            tags.get(name)
+                .copied()
+                .ok_or_else(|| format!("Missing tag {}", name))
+        };
+        fn parse_tag<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid {} '{}'", name, value))
+        }
+        let rules = Rules {
+            cards_cnt_when_taking_from_stack: parse_tag("Take", tag("Take")?)?,
+            allow_put_three: parse_tag("PutThree", tag("PutThree")?)?,
+            nine_on_table: parse_tag("NineOnTable", tag("NineOnTable")?)?,
+            nine_of_hearts_starts: parse_tag("NineOfHeartsStarts", tag("NineOfHeartsStarts")?)?,
+        };
+        if rules.cards_cnt_when_taking_from_stack == 0 {
+            return Err("Invalid Take '0'".to_string());
+        }
+        let winner = match tag("Result")? {
+            "player" => Some(Turn::Player),
+            "opponent" => Some(Turn::Opponent),
+            "*" => None,
+            r => return Err(format!("Invalid Result '{}'", r)),
+        };
+        let record = Self {
+            rules,
+            opponent: tag("Opponent")?.parse()?,
+            seed: parse_tag("Seed", tag("Seed")?)?,
+            start: VerboseState::parse(tag("Position")?, &rules)
+                .map_err(|err| format!("Invalid Position: {}", err))?,
+            moves,
+            winner,
+            started: parse_tag("Started", tag("Started")?)?,
+            ended: tags
+                .get("Ended")
+                .map(|ended| parse_tag("Ended", ended))
+                .transpose()?,
+        };
+
+        let positions = record.positions()?;
+        if positions.last().unwrap().winner() != record.winner {
+            return Err(format!(
+                "Result '{}' does not follow from the moves",
+                tag("Result")?
+            ));
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(moves: &str) -> GameRecord {
+        let rules = Rules::STANDARD;
+        let mut record = GameRecord::new(
+            rules,
+            "optimal".parse().unwrap(),
+            42,
+            VerboseState::initial(&rules),
+        );
+        for m in moves.split_whitespace() {
+            record.push(m.parse().unwrap());
+        }
+        record
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let mut finished = record("9 9 1 T");
+        finished.end(None);
+        for record in [record(""), record("9 9"), finished] {
+            let parsed = record.to_string().parse::<GameRecord>().unwrap();
+            assert_eq!(parsed.to_string(), record.to_string());
+        }
+    }
+
+    #[test]
+    fn parse_all_splits_games_without_moves() {
+        let games = [record(""), record("9"), record(""), record("9 9 1 T")];
+        let mut file = String::new();
+        for game in &games {
+            file += &format!("{}\n\n", game);
+        }
+
+        let parsed = GameRecord::parse_all(&file).unwrap();
+        assert_eq!(parsed.len(), games.len());
+        for (parsed, game) in parsed.iter().zip(&games) {
+            assert_eq!(parsed.moves, game.moves);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_illegal_moves_and_wrong_results() {
+        let legal = record("9 9 1 T").to_string();
+        let illegal = legal.replace("9 9 1 T", "9 1 9 T");
+        assert_eq!(
+            illegal.parse::<GameRecord>().unwrap_err(),
+            "Move 3 (9): Cannot put 9 onto 1"
+        );
+        let wrong_result = legal.replace("[Result \"*\"]", "[Result \"player\"]");
+        assert_eq!(
+            wrong_result.parse::<GameRecord>().unwrap_err(),
+            "Result 'player' does not follow from the moves"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::state::CardsHand;
+use std::cmp::Ordering;
+
+/// Rule variant the game is played with.
+///
+/// Every function generating, encoding or solving states takes the rules explicitly,
+/// because the same `State` means different positions under different rules.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Rules {
+    /// How many top cards are taken from the table stack instead of putting any card.
+    /// Must be at least 1.
+    pub cards_cnt_when_taking_from_stack: u8,
+    /// Whether three cards of the same rank can be put at once
+    /// (besides a single card and all cards of the rank).
+    pub allow_put_three: bool,
+    /// Whether one nine lies on the table from the start and is never in play, so only 23 cards
+    /// are dealt. Otherwise all 24 cards are dealt and the table starts empty.
+    pub nine_on_table: bool,
+    /// Whether the game opens with the holder of the nine of hearts putting it alone.
+    /// That nine stays at the bottom of the table stack and can never be taken.
+    /// Only matters without `nine_on_table`, where it is the nine lying on the table anyway.
+    pub nine_of_hearts_starts: bool,
+}
+
+impl Rules {
+    pub const STANDARD: Self = Self {
+        cards_cnt_when_taking_from_stack: 3,
+        allow_put_three: false,
+        nine_on_table: true,
+        nine_of_hearts_starts: false,
+    };
+
+    /// Number of cards of given rank in play.
+    pub fn card_idx_to_cnt(&self, idx: usize) -> usize {
+        match idx.cmp(&(CardsHand::CARD_TYPES - 1)) {
+            Ordering::Less => 4,
+            Ordering::Equal if self.nine_on_table => 3,
+            Ordering::Equal => 4,
+            Ordering::Greater => panic!("Invalid idx"),
+        }
+    }
+
+    /// Identifies the rules in files built for them, such as the solver cache.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        [
+            self.cards_cnt_when_taking_from_stack,
+            u8::from(self.allow_put_three),
+            u8::from(self.nine_on_table),
+            u8::from(self.nine_of_hearts_starts),
+        ]
+    }
+
+    /// Whether the bottom nine of a non-empty table stack can never be taken.
+    pub fn is_bottom_nine_fixed(&self) -> bool {
+        !self.nine_on_table && self.nine_of_hearts_starts
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
@@ -1,12 +1,12 @@
-use lazy_static::lazy_static;
-use rand::{seq::SliceRandom, thread_rng};
-use std::{
-    cmp::{min, Ordering},
-    collections::HashMap,
-    convert::TryFrom,
+use crate::{
+    rules::Rules,
+    suited::{Card, SuitedHand},
 };
+use lazy_static::lazy_static;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{cmp::min, collections::HashMap, convert::TryFrom, fmt, str::FromStr};
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct State {
     cards: [u8; 3],
     turn: Turn,
@@ -28,7 +28,7 @@ impl Turn {
 }
 
 // TODO(consider): make "UncheckedVerboseState". Then "VerboseState" is simply a newtype with Deref.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct VerboseState {
     pub player_hand: CardsHand,
     pub opponent_hand: CardsHand,
@@ -36,95 +36,102 @@ pub struct VerboseState {
     pub turn: Turn,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Move {
     pub state: VerboseState,
     pub desc: MoveDescription,
 }
 
-#[derive(Clone)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum MoveDescription {
     PutSingle(usize),
+    /// Only with `Rules::allow_put_three`.
+    PutThree(usize),
     PutAll(usize),
     Take,
 }
 
-const CARDS_CNT_WHEN_TAKING_FROM_STACK: usize = 3;
-
 impl VerboseState {
-    pub fn possible_moves(&self) -> Vec<Move> {
-        let mut res = vec![];
+    pub fn possible_moves(&self, rules: &Rules) -> Vec<Move> {
         if self.is_game_finished() {
-            return res;
+            return vec![];
         }
 
-        let cur_hand = self.get_current_hand();
-
-        for i in 0..CardsHand::CARD_TYPES {
-            // Put 1 card
-            if cur_hand.cards[i] > 0 {
-                let mut s = self.clone();
-                s.get_current_hand_mut().cards[i] -= 1;
-                s.table_stack.cards[i] += 1;
-                s.turn = s.turn.next();
-                res.push(Move {
-                    state: s,
-                    desc: MoveDescription::PutSingle(i),
-                });
-            }
-
-            // Put 4 cards (or 3 nines)
-            let all_cards_cnt = u8::try_from(CardsHand::card_idx_to_cnt(i)).unwrap();
-            if cur_hand.cards[i] == all_cards_cnt {
+        self.get_current_hand()
+            .moves_onto(&self.table_stack, rules)
+            .into_iter()
+            .map(|(desc, hand, table_stack)| {
                 let mut s = self.clone();
-                s.get_current_hand_mut().cards[i] -= all_cards_cnt;
-                s.table_stack.cards[i] += all_cards_cnt;
+                *s.get_current_hand_mut() = hand;
+                s.table_stack = table_stack;
                 s.turn = s.turn.next();
-                res.push(Move {
-                    state: s,
-                    desc: MoveDescription::PutAll(i),
-                });
-            }
+                Move { state: s, desc }
+            })
+            .collect()
+    }
 
-            if self.table_stack.cards[i] != 0 {
-                // Can't put weaker cards
-                break;
-            }
+    /// State after given move, or why it is illegal.
+    pub fn apply_move(&self, desc: MoveDescription, rules: &Rules) -> Result<Self, String> {
+        if let Some(m) = self
+            .possible_moves(rules)
+            .into_iter()
+            .find(|m| m.desc == desc)
+        {
+            return Ok(m.state);
         }
 
+        if let MoveDescription::PutSingle(i)
+        | MoveDescription::PutThree(i)
+        | MoveDescription::PutAll(i) = desc
         {
-            // Take up to 3 top cards
-            let mut s = self.clone();
-            let cards_cnt_when_taking_from_stack =
-                u8::try_from(CARDS_CNT_WHEN_TAKING_FROM_STACK).unwrap();
-            let mut to_remove_yet = cards_cnt_when_taking_from_stack;
-            for i in 0..CardsHand::CARD_TYPES {
-                let rm_cur = min(to_remove_yet, s.table_stack.cards[i]);
-                to_remove_yet -= rm_cur;
-                s.table_stack.cards[i] -= rm_cur;
-                s.get_current_hand_mut().cards[i] += rm_cur;
-                if to_remove_yet == 0 {
-                    break;
-                }
+            if i >= CardsHand::CARD_TYPES {
+                return Err(format!("Unknown rank index {}", i));
             }
-            s.turn = s.turn.next();
-            if to_remove_yet < cards_cnt_when_taking_from_stack {
-                res.push(Move {
-                    state: s,
-                    desc: MoveDescription::Take,
-                });
+        }
+        if self.is_game_finished() {
+            return Err("Game already finished".to_string());
+        }
+        let (i, cnt) = match desc {
+            MoveDescription::Take => {
+                return Err("No card can be taken from the table stack".to_string());
             }
+            MoveDescription::PutSingle(i) => (i, 1),
+            MoveDescription::PutThree(_) if !rules.allow_put_three => {
+                return Err("Putting three cards is not allowed".to_string());
+            }
+            MoveDescription::PutThree(i) if rules.card_idx_to_cnt(i) == 3 => {
+                return Err(format!("Only all {} can be put", CardsHand::IDX_TO_CHAR[i]));
+            }
+            MoveDescription::PutThree(i) => (i, 3),
+            MoveDescription::PutAll(i) => (i, rules.card_idx_to_cnt(i)),
+        };
+        let card = CardsHand::IDX_TO_CHAR[i];
+        let in_hand = usize::from(self.get_current_hand().cards[i]);
+        if in_hand < cnt {
+            return Err(format!("{} {} needed, {} in hand", cnt, card, in_hand));
         }
-
-        res
+        if self.table_stack.is_empty() {
+            return Err("The game has to be opened with the nine of hearts".to_string());
+        }
+        let top = (0..CardsHand::CARD_TYPES)
+            .find(|&i| self.table_stack.cards[i] > 0)
+            .unwrap();
+        Err(format!(
+            "Cannot put {} onto {}",
+            card,
+            CardsHand::IDX_TO_CHAR[top]
+        ))
     }
 
     #[allow(dead_code)] // Was used in playground.
-    pub fn following_states(&self) -> Vec<Self> {
-        self.possible_moves().into_iter().map(|m| m.state).collect()
+    pub fn following_states(&self, rules: &Rules) -> Vec<Self> {
+        self.possible_moves(rules)
+            .into_iter()
+            .map(|m| m.state)
+            .collect()
     }
 
-    pub fn preceding_states(&self) -> Vec<Self> {
+    pub fn preceding_states(&self, rules: &Rules) -> Vec<Self> {
         let mut res = vec![];
 
         // Clone to easier access "previous hand"
@@ -142,34 +149,42 @@ impl VerboseState {
         }
 
         if let Some(i) = top_card {
+            let mut push_if_valid = |cnt: u8| {
+                let mut s = selff.clone();
+                s.get_current_hand_mut().cards[i] += cnt;
+                s.table_stack.cards[i] -= cnt;
+                // Only the nine of hearts can be put on an empty table then.
+                let valid_opening = !rules.is_bottom_nine_fixed()
+                    || s.table_stack != CardsHand::EMPTY
+                    || (i == CardsHand::NINES_IDX && cnt == 1);
+                if valid_opening && !s.is_game_finished() {
+                    res.push(s);
+                }
+            };
+
             // Put 1 card
-            let mut s = selff.clone();
-            s.get_current_hand_mut().cards[i] += 1;
-            s.table_stack.cards[i] -= 1;
-            if !s.is_game_finished() {
-                res.push(s);
+            push_if_valid(1);
+
+            // Put 3 cards (if not all of them)
+            let all_cards_cnt = u8::try_from(rules.card_idx_to_cnt(i)).unwrap();
+            if rules.allow_put_three && all_cards_cnt != 3 && selff.table_stack.cards[i] >= 3 {
+                push_if_valid(3);
             }
 
             // Put 4 cards (or 3 nines)
-            let all_cards_cnt = u8::try_from(CardsHand::card_idx_to_cnt(i)).unwrap();
             if selff.table_stack.cards[i] == all_cards_cnt {
-                let mut s = selff.clone();
-                s.get_current_hand_mut().cards[i] += all_cards_cnt;
-                s.table_stack.cards[i] -= all_cards_cnt;
-                if !s.is_game_finished() {
-                    res.push(s);
-                }
+                push_if_valid(all_cards_cnt);
             }
         }
 
         // Take up to 3 top cards
-        {
+        if selff.table_stack != CardsHand::EMPTY || !rules.is_bottom_nine_fixed() {
             fn process_one_card(
                 res: &mut Vec<VerboseState>,
                 vs: &mut VerboseState,
                 top_card: usize,
                 all_cards_req: bool,
-                mut cards_left: usize,
+                mut cards_left: u8,
             ) {
                 cards_left -= 1;
                 for i in 0..=top_card {
@@ -192,17 +207,17 @@ impl VerboseState {
                 }
             }
 
-            let (top_card, all_cards_req) = match top_card {
-                Some(c) => (c, true),
-                None => (CardsHand::CARD_TYPES - 1, false),
-            };
+            // If some cards could still be taken, the maximum number of them had to be taken.
+            let all_cards_req =
+                (0..CardsHand::CARD_TYPES).any(|i| selff.table_stack.takeable_cnt(i, rules) > 0);
+            let top_card = top_card.unwrap_or(CardsHand::CARD_TYPES - 1);
             let mut selff = selff;
             process_one_card(
                 &mut res,
                 &mut selff,
                 top_card,
                 all_cards_req,
-                CARDS_CNT_WHEN_TAKING_FROM_STACK,
+                rules.cards_cnt_when_taking_from_stack,
             );
         }
 
@@ -213,6 +228,49 @@ impl VerboseState {
         self.player_hand == CardsHand::EMPTY || self.opponent_hand == CardsHand::EMPTY
     }
 
+    /// Checks what every state reachable from a deal satisfies: all cards of every rank
+    /// are in play, at most one side got rid of its cards, and the nine of hearts stays
+    /// at the bottom of the table stack if it cannot be taken, so it is held by the side
+    /// opening the game.
+    pub fn validate(&self, rules: &Rules) -> Result<(), &'static str> {
+        for i in 0..CardsHand::CARD_TYPES {
+            let cnt = usize::from(self.player_hand.cards[i])
+                + usize::from(self.opponent_hand.cards[i])
+                + usize::from(self.table_stack.cards[i]);
+            if cnt != rules.card_idx_to_cnt(i) {
+                return Err("Wrong number of cards of some rank");
+            }
+        }
+        if self.player_hand.is_empty() && self.opponent_hand.is_empty() {
+            return Err("Both sides without cards");
+        }
+        if rules.is_bottom_nine_fixed()
+            && !self.table_stack.is_empty()
+            && self.table_stack.cards[CardsHand::NINES_IDX] == 0
+        {
+            return Err("No nine at the bottom of the table stack");
+        }
+        if rules.is_bottom_nine_fixed()
+            && self.table_stack.is_empty()
+            && !self.is_game_finished()
+            && self.get_current_hand().cards[CardsHand::NINES_IDX] == 0
+        {
+            return Err("The side to move cannot open with the nine of hearts");
+        }
+        Ok(())
+    }
+
+    /// The side which got rid of all its cards.
+    pub fn winner(&self) -> Option<Turn> {
+        if self.player_hand == CardsHand::EMPTY {
+            Some(Turn::Player)
+        } else if self.opponent_hand == CardsHand::EMPTY {
+            Some(Turn::Opponent)
+        } else {
+            None
+        }
+    }
+
     fn get_current_hand(&self) -> &CardsHand {
         match self.turn {
             Turn::Player => &self.player_hand,
@@ -228,16 +286,104 @@ impl VerboseState {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct CardsHand {
     /// Sorted as: [Aces, Kings, Queens, Jacks, Tens, Nines].
-    /// There are up to 3 nines (assuming one is always on the table).
+    /// There are up to 3 nines if one is always on the table (see `Rules::nine_on_table`).
     pub cards: [u8; Self::CARD_TYPES],
 }
 
 impl CardsHand {
     pub const CARD_TYPES: usize = 6;
+    pub const NINES_IDX: usize = Self::CARD_TYPES - 1;
     pub const IDX_TO_CHAR: [char; Self::CARD_TYPES] = ['A', 'K', 'Q', 'J', '1', '9'];
+
+    /// Moves of the player holding this hand, together with the hand and the table stack
+    /// after each of them.
+    pub fn moves_onto(
+        &self,
+        table_stack: &CardsHand,
+        rules: &Rules,
+    ) -> Vec<(MoveDescription, CardsHand, CardsHand)> {
+        let mut res = vec![];
+
+        let put_cards = |i: usize, cnt: u8, desc: MoveDescription| {
+            let (mut hand, mut table_stack) = (self.clone(), table_stack.clone());
+            hand.cards[i] -= cnt;
+            table_stack.cards[i] += cnt;
+            (desc, hand, table_stack)
+        };
+
+        if *table_stack == CardsHand::EMPTY && rules.is_bottom_nine_fixed() {
+            // Opening with the nine of hearts
+            if self.cards[Self::NINES_IDX] > 0 {
+                let desc = MoveDescription::PutSingle(Self::NINES_IDX);
+                res.push(put_cards(Self::NINES_IDX, 1, desc));
+            }
+            return res;
+        }
+
+        for i in 0..Self::CARD_TYPES {
+            // Put 1 card
+            if self.cards[i] > 0 {
+                res.push(put_cards(i, 1, MoveDescription::PutSingle(i)));
+            }
+
+            // Put 3 cards (if not all of them)
+            let all_cards_cnt = u8::try_from(rules.card_idx_to_cnt(i)).unwrap();
+            if rules.allow_put_three && all_cards_cnt != 3 && self.cards[i] >= 3 {
+                res.push(put_cards(i, 3, MoveDescription::PutThree(i)));
+            }
+
+            // Put 4 cards (or 3 nines)
+            if self.cards[i] == all_cards_cnt {
+                res.push(put_cards(i, all_cards_cnt, MoveDescription::PutAll(i)));
+            }
+
+            if table_stack.cards[i] != 0 {
+                // Can't put weaker cards
+                break;
+            }
+        }
+
+        {
+            // Take up to 3 top cards
+            let (mut hand, mut table_stack) = (self.clone(), table_stack.clone());
+            let cards_cnt_when_taking_from_stack = rules.cards_cnt_when_taking_from_stack;
+            let mut to_remove_yet = cards_cnt_when_taking_from_stack;
+            for i in 0..Self::CARD_TYPES {
+                let rm_cur = min(to_remove_yet, table_stack.takeable_cnt(i, rules));
+                to_remove_yet -= rm_cur;
+                table_stack.cards[i] -= rm_cur;
+                hand.cards[i] += rm_cur;
+                if to_remove_yet == 0 {
+                    break;
+                }
+            }
+            if to_remove_yet < cards_cnt_when_taking_from_stack {
+                res.push((MoveDescription::Take, hand, table_stack));
+            }
+        }
+
+        res
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.iter().map(|&c| usize::from(c)).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+
+    /// Number of cards of given rank which can be taken from this table stack.
+    fn takeable_cnt(&self, idx: usize, rules: &Rules) -> u8 {
+        if idx == Self::NINES_IDX && rules.is_bottom_nine_fixed() {
+            self.cards[idx].saturating_sub(1)
+        } else {
+            self.cards[idx]
+        }
+    }
 }
 
 // ================ CONVERSIONS ======================
@@ -271,34 +417,24 @@ fn gen_card_distr(sum: u8) -> Vec<(u8, u8, u8)> {
     res
 }
 
-impl CardsHand {
-    fn card_idx_to_distr(idx: usize) -> &'static Vec<(u8, u8, u8)> {
-        match idx.cmp(&(Self::CARD_TYPES - 1)) {
-            Ordering::Less => &*CARD_DISTR_4,
-            Ordering::Equal => &*CARD_DISTR_3,
-            Ordering::Greater => panic!("Invalid idx"),
-        }
+fn card_idx_to_distr(idx: usize, rules: &Rules) -> &'static Vec<(u8, u8, u8)> {
+    match rules.card_idx_to_cnt(idx) {
+        4 => &CARD_DISTR_4,
+        3 => &CARD_DISTR_3,
+        _ => unreachable!(),
     }
+}
 
-    fn card_idx_to_rev_distr(idx: usize) -> &'static HashMap<(u8, u8, u8), u8> {
-        match idx.cmp(&(Self::CARD_TYPES - 1)) {
-            Ordering::Less => &*REV_CARD_DISTR_4,
-            Ordering::Equal => &*REV_CARD_DISTR_3,
-            Ordering::Greater => panic!("Invalid idx"),
-        }
-    }
-
-    pub fn card_idx_to_cnt(idx: usize) -> usize {
-        match idx.cmp(&(Self::CARD_TYPES - 1)) {
-            Ordering::Less => 4,
-            Ordering::Equal => 3,
-            Ordering::Greater => panic!("Invalid idx"),
-        }
+fn card_idx_to_rev_distr(idx: usize, rules: &Rules) -> &'static HashMap<(u8, u8, u8), u8> {
+    match rules.card_idx_to_cnt(idx) {
+        4 => &REV_CARD_DISTR_4,
+        3 => &REV_CARD_DISTR_3,
+        _ => unreachable!(),
     }
 }
 
-impl From<State> for VerboseState {
-    fn from(s: State) -> Self {
+impl VerboseState {
+    pub fn from_state(s: State, rules: &Rules) -> Self {
         let mut vs = VerboseState {
             player_hand: CardsHand::EMPTY,
             opponent_hand: CardsHand::EMPTY,
@@ -308,7 +444,7 @@ impl From<State> for VerboseState {
 
         for i in 0..CardsHand::CARD_TYPES {
             let card_code = (usize::from(s.cards[i / 2]) >> (i % 2 * 4)) & 0xF;
-            let distr = CardsHand::card_idx_to_distr(i);
+            let distr = card_idx_to_distr(i, rules);
             assert!(card_code < distr.len(), "Invalid state");
             let card_distr = distr[card_code];
             vs.player_hand.cards[i] = card_distr.0;
@@ -320,10 +456,8 @@ impl From<State> for VerboseState {
     }
 }
 
-impl TryFrom<&VerboseState> for State {
-    type Error = &'static str;
-
-    fn try_from(vs: &VerboseState) -> Result<Self, Self::Error> {
+impl State {
+    pub fn from_verbose(vs: &VerboseState, rules: &Rules) -> Result<Self, &'static str> {
         let mut s = State {
             cards: [0; 3],
             turn: vs.turn,
@@ -335,7 +469,7 @@ impl TryFrom<&VerboseState> for State {
                 vs.opponent_hand.cards[i],
                 vs.table_stack.cards[i],
             );
-            let distr = CardsHand::card_idx_to_rev_distr(i);
+            let distr = card_idx_to_rev_distr(i, rules);
             if !distr.contains_key(&key) {
                 return Err("Invalid state");
             }
@@ -345,13 +479,49 @@ impl TryFrom<&VerboseState> for State {
 
         Ok(s)
     }
-}
 
-impl TryFrom<VerboseState> for State {
-    type Error = &'static str;
+    pub fn turn(self) -> Turn {
+        self.turn
+    }
+
+    /// Number of all states (valid or not) under given rules.
+    pub fn count(rules: &Rules) -> usize {
+        (0..CardsHand::CARD_TYPES)
+            .map(|i| card_idx_to_distr(i, rules).len())
+            .product::<usize>()
+            * 2
+    }
+
+    /// Dense index of the state, in `0..State::count(rules)`.
+    pub fn index(self, rules: &Rules) -> usize {
+        let mut idx = 0;
+        for i in 0..CardsHand::CARD_TYPES {
+            let card_code = (usize::from(self.cards[i / 2]) >> (i % 2 * 4)) & 0xF;
+            idx = idx * card_idx_to_distr(i, rules).len() + card_code;
+        }
+        idx * 2 + self.turn as usize
+    }
 
-    fn try_from(vs: VerboseState) -> Result<Self, Self::Error> {
-        TryFrom::try_from(&vs)
+    pub fn from_index(mut idx: usize, rules: &Rules) -> Self {
+        assert!(idx < Self::count(rules), "Invalid index");
+        let turn = if idx.is_multiple_of(2) {
+            Turn::Player
+        } else {
+            Turn::Opponent
+        };
+        idx /= 2;
+
+        let mut s = State {
+            cards: [0; 3],
+            turn,
+        };
+        for i in (0..CardsHand::CARD_TYPES).rev() {
+            let distr_len = card_idx_to_distr(i, rules).len();
+            let card_code = u8::try_from(idx % distr_len).unwrap();
+            idx /= distr_len;
+            s.cards[i / 2] |= card_code << (i % 2 * 4);
+        }
+        s
     }
 }
 
@@ -362,13 +532,14 @@ impl CardsHand {
 
 impl VerboseState {
     #[allow(dead_code)]
-    pub fn initial() -> Self {
+    pub fn initial(rules: &Rules) -> Self {
+        let opponent_nines = if rules.nine_on_table { 1 } else { 2 };
         Self {
             player_hand: CardsHand {
                 cards: [2, 2, 2, 2, 2, 2],
             },
             opponent_hand: CardsHand {
-                cards: [2, 2, 2, 2, 2, 1],
+                cards: [2, 2, 2, 2, 2, opponent_nines],
             },
             table_stack: CardsHand {
                 cards: [0, 0, 0, 0, 0, 0],
@@ -377,9 +548,58 @@ impl VerboseState {
         }
     }
 
-    /// Might return state unreachable from initial one.
-    pub fn random() -> Self {
-        let mut rng = thread_rng();
+    /// Deals a shuffled deck as in a real game, leaving the nine of hearts on the table
+    /// with `Rules::nine_on_table`. The side moving first gets the odd card, and holds
+    /// the nine of hearts if the game has to start with it.
+    pub fn deal(rules: &Rules) -> Self {
+        Self::deal_with_rng(rules, &mut thread_rng())
+    }
+
+    /// `deal` with given source of randomness, e.g. a seeded one to get the same deal again.
+    pub fn deal_with_rng(rules: &Rules, rng: &mut impl Rng) -> Self {
+        let mut deck = SuitedHand::full_deck().iter().collect::<Vec<_>>();
+        if rules.nine_on_table {
+            deck.retain(|&c| c != Card::NINE_OF_HEARTS);
+        }
+        deck.shuffle(rng);
+
+        let (first, second) = deck.split_at(deck.len().div_ceil(2));
+        let first = first.iter().copied().collect::<SuitedHand>();
+        let second = second.iter().copied().collect::<SuitedHand>();
+        let turn = if rules.is_bottom_nine_fixed() {
+            if first.contains(Card::NINE_OF_HEARTS) {
+                Turn::Player
+            } else {
+                Turn::Opponent
+            }
+        } else if rng.gen() {
+            Turn::Player
+        } else {
+            Turn::Opponent
+        };
+        // Without a nine on the table both halves are equal, so only the nine of hearts
+        // decides who opens.
+        let (player_hand, opponent_hand) = if rules.is_bottom_nine_fixed() || turn == Turn::Player {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        Self {
+            player_hand: player_hand.to_cards_hand(),
+            opponent_hand: opponent_hand.to_cards_hand(),
+            table_stack: CardsHand::EMPTY,
+            turn,
+        }
+    }
+
+    /// Might return state unreachable from initial one, see `deal` for one that is not.
+    pub fn random(rules: &Rules) -> Self {
+        Self::random_with_rng(rules, &mut thread_rng())
+    }
+
+    /// `random` with given source of randomness.
+    pub fn random_with_rng(rules: &Rules, rng: &mut impl Rng) -> Self {
         let mut vs = Self {
             player_hand: CardsHand::EMPTY,
             opponent_hand: CardsHand::EMPTY,
@@ -388,8 +608,8 @@ impl VerboseState {
         };
 
         for i in 0..CardsHand::CARD_TYPES {
-            let distr = CardsHand::card_idx_to_distr(i);
-            let card_distr = distr.as_slice().choose(&mut rng).unwrap();
+            let distr = card_idx_to_distr(i, rules);
+            let card_distr = distr.as_slice().choose(rng).unwrap();
             vs.player_hand.cards[i] = card_distr.0;
             vs.opponent_hand.cards[i] = card_distr.1;
             vs.table_stack.cards[i] = card_distr.2;
@@ -398,3 +618,372 @@ impl VerboseState {
         vs
     }
 }
+
+// ===================== NOTATION ===================
+/// Cards from the lowest rank, e.g. `991JQKA`, tens written as `1`.
+impl fmt::Display for CardsHand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in (0..Self::CARD_TYPES).rev() {
+            for _ in 0..self.cards[i] {
+                write!(f, "{}", Self::IDX_TO_CHAR[i])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inverse of `Display`, accepting cards in any order.
+impl FromStr for CardsHand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hand = Self::EMPTY;
+        for (pos, c) in s.chars().enumerate() {
+            let i = Self::IDX_TO_CHAR
+                .iter()
+                .position(|&x| x == c)
+                .ok_or_else(|| format!("Unknown card '{}' at position {}", c, pos + 1))?;
+            hand.cards[i] = hand.cards[i].saturating_add(1);
+        }
+        Ok(hand)
+    }
+}
+
+/// `PLAYER|TABLE|OPPONENT TURN`, e.g. `991JQKA|11J|99QKKA p`: both hands and the table
+/// stack (`-` for no cards), followed by the side to move (`p` or `o`). With
+/// `Rules::nine_on_table`, the nine lying on the table from the start is left out.
+impl fmt::Display for VerboseState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, hand) in [&self.player_hand, &self.table_stack, &self.opponent_hand]
+            .into_iter()
+            .enumerate()
+        {
+            if i > 0 {
+                write!(f, "|")?;
+            }
+            if hand.is_empty() {
+                write!(f, "-")?;
+            } else {
+                write!(f, "{}", hand)?;
+            }
+        }
+        match self.turn {
+            Turn::Player => write!(f, " p"),
+            Turn::Opponent => write!(f, " o"),
+        }
+    }
+}
+
+/// Inverse of `Display`, without checking the state against any rules (see `parse`).
+impl FromStr for VerboseState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (cards, turn) = s
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| "Missing side to move (p or o) after the cards".to_string())?;
+        let turn = match turn {
+            "p" => Turn::Player,
+            "o" => Turn::Opponent,
+            t => return Err(format!("Unknown side to move '{}', expected p or o", t)),
+        };
+        let parts = cards.trim().split('|').collect::<Vec<_>>();
+        let [player, table, opponent] = parts[..] else {
+            return Err(format!(
+                "Expected 3 parts separated by '|', found {}",
+                parts.len()
+            ));
+        };
+        let hand = |s: &str, what: &str| match s {
+            "-" => Ok(CardsHand::EMPTY),
+            s => s.parse().map_err(|err| format!("{} of {}", err, what)),
+        };
+
+        Ok(Self {
+            player_hand: hand(player, "player's hand")?,
+            opponent_hand: hand(opponent, "opponent's hand")?,
+            table_stack: hand(table, "table stack")?,
+            turn,
+        })
+    }
+}
+
+/// `K` for putting a king, `3K` for putting three and `4K` for putting all of them
+/// (even if there are only three nines), `T` for taking from the table stack.
+impl fmt::Display for MoveDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MoveDescription::PutSingle(i) => write!(f, "{}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::PutThree(i) => write!(f, "3{}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::PutAll(i) => write!(f, "4{}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::Take => write!(f, "T"),
+        }
+    }
+}
+
+impl MoveDescription {
+    /// The move in words, e.g. `put three K`.
+    pub fn describe(&self) -> String {
+        match *self {
+            MoveDescription::PutSingle(i) => format!("put {}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::PutThree(i) => format!("put three {}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::PutAll(i) => format!("put all {}", CardsHand::IDX_TO_CHAR[i]),
+            MoveDescription::Take => "take".to_string(),
+        }
+    }
+}
+
+/// Inverse of `Display`.
+impl FromStr for MoveDescription {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let card_idx = |c: char| {
+            CardsHand::IDX_TO_CHAR
+                .iter()
+                .position(|&x| x == c)
+                .ok_or_else(|| format!("Unknown card '{}' in move '{}'", c, s))
+        };
+        let chars = s.chars().collect::<Vec<_>>();
+        match chars[..] {
+            ['T'] => Ok(MoveDescription::Take),
+            [c] => Ok(MoveDescription::PutSingle(card_idx(c)?)),
+            ['3', c] => Ok(MoveDescription::PutThree(card_idx(c)?)),
+            ['4', c] => Ok(MoveDescription::PutAll(card_idx(c)?)),
+            _ => Err(format!(
+                "Invalid move '{}', expected e.g. K, 3K, 4K or T",
+                s
+            )),
+        }
+    }
+}
+
+impl VerboseState {
+    /// Parses the notation of `Display` and checks the state is valid under given rules.
+    pub fn parse(s: &str, rules: &Rules) -> Result<Self, String> {
+        let vs = s.parse::<Self>()?;
+        for i in 0..CardsHand::CARD_TYPES {
+            let cnt = usize::from(vs.player_hand.cards[i])
+                + usize::from(vs.opponent_hand.cards[i])
+                + usize::from(vs.table_stack.cards[i]);
+            if cnt != rules.card_idx_to_cnt(i) {
+                return Err(format!(
+                    "{} cards of rank {}, expected {}",
+                    cnt,
+                    CardsHand::IDX_TO_CHAR[i],
+                    rules.card_idx_to_cnt(i)
+                ));
+            }
+        }
+        vs.validate(rules)?;
+        Ok(vs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const FIXED_NINE: Rules = Rules {
+        nine_on_table: false,
+        nine_of_hearts_starts: true,
+        ..Rules::STANDARD
+    };
+
+    #[test]
+    fn cards_hand_notation_round_trips() {
+        for s in ["", "9", "991JQKA", "99911JJJQQQQKKKKAAAA"] {
+            assert_eq!(s.parse::<CardsHand>().unwrap().to_string(), s);
+        }
+        assert_eq!("".parse::<CardsHand>().unwrap(), CardsHand::EMPTY);
+        // Any order is accepted, lowest rank first is printed.
+        assert_eq!("AK9".parse::<CardsHand>().unwrap().to_string(), "9KA");
+    }
+
+    #[test]
+    fn cards_hand_rejects_unknown_card() {
+        assert_eq!(
+            "99X".parse::<CardsHand>(),
+            Err("Unknown card 'X' at position 3".to_string())
+        );
+    }
+
+    #[test]
+    fn verbose_state_notation_round_trips() {
+        for s in ["991JQKA|11J|99QKKA p", "-|9|1 o", "99|-|- p"] {
+            assert_eq!(s.parse::<VerboseState>().unwrap().to_string(), s);
+        }
+
+        let vs = "-|9|1 o".parse::<VerboseState>().unwrap();
+        assert_eq!(vs.player_hand, CardsHand::EMPTY);
+        assert_eq!(vs.table_stack.cards[CardsHand::NINES_IDX], 1);
+        assert_eq!(vs.turn, Turn::Opponent);
+
+        let initial = VerboseState::initial(&Rules::STANDARD);
+        assert_eq!(initial.to_string(), "9911JJQQKKAA|-|911JJQQKKAA p");
+        assert_eq!(
+            VerboseState::parse(&initial.to_string(), &Rules::STANDARD),
+            Ok(initial)
+        );
+    }
+
+    #[test]
+    fn verbose_state_rejects_malformed_notation() {
+        let err = |s: &str| s.parse::<VerboseState>().unwrap_err();
+        assert_eq!(
+            err("991JQKA|11J|99QKKA"),
+            "Missing side to move (p or o) after the cards"
+        );
+        assert_eq!(err("9|1|J x"), "Unknown side to move 'x', expected p or o");
+        assert_eq!(err("9|1 p"), "Expected 3 parts separated by '|', found 2");
+        assert_eq!(
+            err("9|1|J|Q p"),
+            "Expected 3 parts separated by '|', found 4"
+        );
+        assert_eq!(
+            err("9|X|J p"),
+            "Unknown card 'X' at position 1 of table stack"
+        );
+        assert_eq!(
+            err("9|-|J0 o"),
+            "Unknown card '0' at position 2 of opponent's hand"
+        );
+    }
+
+    #[test]
+    fn parse_checks_cards_against_rules() {
+        assert_eq!(
+            VerboseState::parse("9911JJQQKKA|-|911JJQQKKAA p", &Rules::STANDARD),
+            Err("3 cards of rank A, expected 4".to_string())
+        );
+        assert_eq!(
+            VerboseState::parse("99911JJQQKKAA|-|911JJQQKKAA p", &Rules::STANDARD),
+            Err("4 cards of rank 9, expected 3".to_string())
+        );
+        assert_eq!(
+            VerboseState::parse("99911JJQQKKAA|-|911JJQQKKAA p", &FIXED_NINE),
+            Ok("99911JJQQKKAA|-|911JJQQKKAA p".parse().unwrap())
+        );
+        assert_eq!(
+            VerboseState::parse("99991JJQQKKAA|-|111JJQQKKAA o", &FIXED_NINE),
+            Err("The side to move cannot open with the nine of hearts".to_string())
+        );
+        assert_eq!(
+            VerboseState::parse("99991JJQQKKAA|1|11JJQQKKAA p", &FIXED_NINE),
+            Err("No nine at the bottom of the table stack".to_string())
+        );
+    }
+
+    #[test]
+    fn dealt_states_are_valid() {
+        for take in [1, 3] {
+            for bits in 0..8 {
+                let rules = Rules {
+                    cards_cnt_when_taking_from_stack: take,
+                    allow_put_three: bits & 1 != 0,
+                    nine_on_table: bits & 2 != 0,
+                    nine_of_hearts_starts: bits & 4 != 0,
+                };
+                for seed in 0..200 {
+                    let vs = VerboseState::deal_with_rng(&rules, &mut StdRng::seed_from_u64(seed));
+                    assert_eq!(vs.validate(&rules), Ok(()), "{} under {:?}", vs, rules);
+                    assert!(!vs.possible_moves(&rules).is_empty(), "{}", vs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn move_notation_round_trips() {
+        for s in ["9", "1", "A", "3K", "41", "49", "T"] {
+            assert_eq!(s.parse::<MoveDescription>().unwrap().to_string(), s);
+        }
+        assert_eq!(
+            "3K".parse::<MoveDescription>(),
+            Ok(MoveDescription::PutThree(1))
+        );
+        assert_eq!(
+            "49".parse::<MoveDescription>(),
+            Ok(MoveDescription::PutAll(CardsHand::NINES_IDX))
+        );
+    }
+
+    #[test]
+    fn move_notation_rejects_malformed_moves() {
+        let err = |s: &str| s.parse::<MoveDescription>().unwrap_err();
+        assert_eq!(err("X"), "Unknown card 'X' in move 'X'");
+        assert_eq!(err("3X"), "Unknown card 'X' in move '3X'");
+        assert_eq!(err("3T"), "Unknown card 'T' in move '3T'");
+        for s in ["", "KK", "5K", "4KK"] {
+            assert_eq!(
+                err(s),
+                format!("Invalid move '{}', expected e.g. K, 3K, 4K or T", s)
+            );
+        }
+    }
+
+    #[test]
+    fn apply_move_plays_legal_moves() {
+        let rules = Rules::STANDARD;
+        let vs = VerboseState::initial(&rules)
+            .apply_move(MoveDescription::PutSingle(CardsHand::NINES_IDX), &rules)
+            .unwrap();
+        assert_eq!(vs.to_string(), "911JJQQKKAA|9|911JJQQKKAA o");
+        let vs = vs.apply_move(MoveDescription::Take, &rules).unwrap();
+        assert_eq!(vs.to_string(), "911JJQQKKAA|-|9911JJQQKKAA p");
+    }
+
+    #[test]
+    fn apply_move_explains_illegal_moves() {
+        let apply = |s: &str, desc: &str, rules: &Rules| {
+            s.parse::<VerboseState>()
+                .unwrap()
+                .apply_move(desc.parse().unwrap(), rules)
+                .unwrap_err()
+        };
+        let initial = "9911JJQQKKAA|-|911JJQQKKAA p";
+        let put_three = Rules {
+            allow_put_three: true,
+            ..Rules::STANDARD
+        };
+        assert_eq!(
+            apply("-|9|11 o", "1", &Rules::STANDARD),
+            "Game already finished"
+        );
+        assert_eq!(
+            apply(initial, "T", &Rules::STANDARD),
+            "No card can be taken from the table stack"
+        );
+        assert_eq!(
+            apply(initial, "3K", &Rules::STANDARD),
+            "Putting three cards is not allowed"
+        );
+        assert_eq!(apply(initial, "39", &put_three), "Only all 9 can be put");
+        assert_eq!(
+            apply(initial, "4A", &Rules::STANDARD),
+            "4 A needed, 2 in hand"
+        );
+        assert_eq!(
+            apply("99911JJQQKKAA|-|911JJQQKKAA p", "A", &FIXED_NINE),
+            "The game has to be opened with the nine of hearts"
+        );
+        assert_eq!(
+            apply("99|K|A p", "9", &Rules::STANDARD),
+            "Cannot put 9 onto K"
+        );
+
+        let vs = VerboseState::initial(&Rules::STANDARD);
+        for desc in [
+            MoveDescription::PutSingle(CardsHand::CARD_TYPES),
+            MoveDescription::PutThree(CardsHand::CARD_TYPES),
+            MoveDescription::PutAll(usize::MAX),
+        ] {
+            assert!(vs
+                .apply_move(desc, &Rules::STANDARD)
+                .unwrap_err()
+                .starts_with("Unknown rank index"));
+        }
+    }
+}
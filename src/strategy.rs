@@ -1,249 +1,300 @@
-use crate::state::{CardsHand, Move, State, Turn, VerboseState};
-use rand::{seq::SliceRandom, thread_rng};
+use crate::{
+    rules::Rules,
+    state::{Move, State, Turn, VerboseState},
+};
+use memmap2::Mmap;
+use rand::{seq::SliceRandom, RngCore};
 use std::{
-    collections::{BTreeMap, HashSet, VecDeque},
-    convert::{TryFrom, TryInto},
-    fs,
+    fs::{self, File},
+    io,
     path::Path,
+    thread,
 };
 
+mod cache_file;
+mod cache_tools;
+mod difficulty;
+mod mcts;
+mod minimax;
+mod solver;
+mod tablebase;
+
+pub use cache_tools::{CacheDiff, CacheStats, Inconsistency, OutcomeCounts};
+pub use difficulty::{Difficulty, EpsilonOptimal, Greedy};
+pub use mcts::{Mcts, MctsBudget};
+pub use minimax::{CardsInHand, Evaluation, Minimax, TableTop, WeakCards};
+
+use tablebase::Tablebase;
+
 pub trait Strategy {
-    fn get_next_move(&self, state: &VerboseState) -> Option<Move>;
+    /// All randomness comes from `rng`, so a seeded one makes the choice reproducible.
+    fn get_next_move(&self, state: &VerboseState, rng: &mut dyn RngCore) -> Option<Move>;
+}
+
+/// Plays the game out between two strategies, e.g. to benchmark one against another.
+/// Returns the winner, or `None` if the game has not finished within `max_plies` plies.
+pub fn play_out(
+    state: &VerboseState,
+    player: &dyn Strategy,
+    opponent: &dyn Strategy,
+    max_plies: usize,
+    rng: &mut dyn RngCore,
+) -> Option<Turn> {
+    let mut state = state.clone();
+    for _ in 0..max_plies {
+        if state.is_game_finished() {
+            break;
+        }
+
+        let strategy = match state.turn {
+            Turn::Player => player,
+            Turn::Opponent => opponent,
+        };
+        state = strategy.get_next_move(&state, rng)?.state;
+    }
+    state.winner()
 }
 
-pub struct Random;
+pub struct Random {
+    pub rules: Rules,
+}
 
 impl Strategy for Random {
-    fn get_next_move(&self, state: &VerboseState) -> Option<Move> {
-        let mut rng = thread_rng();
-        state.possible_moves().as_slice().choose(&mut rng).cloned()
+    fn get_next_move(&self, state: &VerboseState, rng: &mut dyn RngCore) -> Option<Move> {
+        state
+            .possible_moves(&self.rules)
+            .as_slice()
+            .choose(rng)
+            .cloned()
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct Optimal<'a> {
     cache: &'a OptimalCache,
 }
 
-// Cannot be put inside `impl Optimal`: it is unstable.
-type OptimalWinningStates = BTreeMap<Option<Turn>, HashSet<State>>;
+/// Result of a state when both sides play optimally.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Outcome {
+    /// `None` for a draw.
+    pub winner: Option<Turn>,
+    /// Plies to the end of the game, the winner hurrying and the loser delaying it.
+    /// Zero for draws.
+    pub plies: u16,
+}
 
 impl<'a> Optimal<'a> {
-    // Consider: paralelize construction? Or maybe keep some cache of states?
+    /// Solves states reachable from `start_state` which are unknown to the cache,
+    /// on `OptimalCache::threads` threads.
     pub fn new_with_mut_cache(start_state: &VerboseState, cache: &'a mut OptimalCache) -> Self {
-        let mut new_reachable_states = HashSet::new();
-        let mut queue = VecDeque::new();
-        let mut winning_queue = VecDeque::new();
-
-        // Phase 1: find all reachable states (unknown to already built cache).
-        let start_state = start_state.try_into().expect("Valid start_state"); // expect: Not ideal, but should be good enough.
-        queue.push_back(start_state);
-        if cache.get_state_winningness(start_state).is_none() {
-            new_reachable_states.insert(start_state);
-        }
-        // No need to classify start_state as winning or losing - there is no move from such starting state anyway.
-        while let Some(s) = queue.pop_front() {
-            let following_states = VerboseState::from(s).possible_moves();
-            for mov in following_states {
-                let s = State::try_from(&mov.state).unwrap();
-                if new_reachable_states.contains(&s) || cache.get_state_winningness(s).is_some() {
-                    continue;
-                }
-                new_reachable_states.insert(s);
-                queue.push_back(s);
-
-                if mov.state.player_hand == CardsHand::EMPTY {
-                    cache
-                        .states
-                        .entry(Some(Turn::Player))
-                        .or_default()
-                        .insert(s);
-                    winning_queue.push_back(s);
-                } else if mov.state.opponent_hand == CardsHand::EMPTY {
-                    cache
-                        .states
-                        .entry(Some(Turn::Opponent))
-                        .or_default()
-                        .insert(s);
-                    winning_queue.push_back(s);
-                }
-            }
-        }
-
-        // Phase 2: propagate down winning states.
-        let add_preceding_states = |queue: &mut VecDeque<_>, s: State| {
-            for vs in VerboseState::from(s).preceding_states() {
-                let s = State::try_from(vs).unwrap();
-                if new_reachable_states.contains(&s) {
-                    queue.push_back(s);
-                }
-            }
-        };
-        while let Some(s) = winning_queue.pop_front() {
-            add_preceding_states(&mut queue, s);
-        }
-
-        let mut winning_cnts = BTreeMap::<_, usize>::new();
-        while let Some(s) = queue.pop_front() {
-            if cache.states[&Some(Turn::Player)].contains(&s)
-                || cache.states[&Some(Turn::Opponent)].contains(&s)
-            {
-                continue;
-            }
-
-            let vs = VerboseState::from(s);
-            let pm = vs.possible_moves();
-            winning_cnts.clear();
-
-            for mov in &pm {
-                let next_s = State::try_from(&mov.state).unwrap();
-                for t in [Turn::Player, Turn::Opponent] {
-                    if cache.states[&Some(t)].contains(&next_s) {
-                        *winning_cnts.entry(t).or_default() += 1;
-                    }
-                }
-            }
-
-            if winning_cnts.get(&vs.turn).copied().unwrap_or_default() > 0 {
-                cache.states.entry(Some(vs.turn)).or_default().insert(s);
-                add_preceding_states(&mut queue, s);
-            } else if winning_cnts
-                .get(&vs.turn.next())
-                .copied()
-                .unwrap_or_default()
-                == pm.len()
-            {
-                cache
-                    .states
-                    .entry(Some(vs.turn.next()))
-                    .or_default()
-                    .insert(s);
-                add_preceding_states(&mut queue, s);
-            }
-        }
-
-        // Add all remaining states as draw ones.
-        for s in &new_reachable_states {
-            if cache.get_state_winningness(*s).is_none() {
-                cache.states.entry(None).or_default().insert(*s);
-            }
-        }
+        assert!(cache.has_plies(), "Optimal play needs plies to the end");
+        let start_state =
+            State::from_verbose(start_state, &cache.rules).expect("Valid start_state"); // expect: Not ideal, but should be good enough.
+        solver::solve_reachable(cache, start_state);
+        Self { cache }
+    }
 
+    /// Solves every valid state under the rules of the cache, not only the ones reachable from
+    /// some start state, so no game needs any further computation.
+    ///
+    /// Takes long and a few hundred MB of memory; meant to be run once, with the cache saved.
+    pub fn solve_all(cache: &'a mut OptimalCache) -> Self {
+        assert!(cache.has_plies(), "Solving needs plies to the end");
+        solver::solve_all(cache);
         Self { cache }
     }
 
+    pub fn rules(&self) -> &Rules {
+        &self.cache.rules
+    }
+
     pub fn get_winning_turn(&self, vs: &VerboseState) -> Option<Turn> {
-        let s = vs.try_into().unwrap();
+        self.get_outcome(vs).winner
+    }
+
+    pub fn get_outcome(&self, vs: &VerboseState) -> Outcome {
+        let s = State::from_verbose(vs, self.rules()).unwrap();
         self.cache
-            .get_state_winningness(s)
+            .get_state_outcome(s)
             .expect("Properly initialized strategy.")
     }
 }
 
 impl<'a> Strategy for Optimal<'a> {
-    fn get_next_move(&self, state: &VerboseState) -> Option<Move> {
+    fn get_next_move(&self, state: &VerboseState, rng: &mut dyn RngCore) -> Option<Move> {
         let (mut win, mut draw, mut lose) = (vec![], vec![], vec![]);
-        let moves = state.possible_moves();
+        let moves = state.possible_moves(self.rules());
         for m in moves {
-            match self.get_winning_turn(&m.state) {
+            let outcome = self.get_outcome(&m.state);
+            match outcome.winner {
                 None => draw.push(m),
-                Some(t) if t == state.turn => win.push(m),
-                _ => lose.push(m),
+                Some(t) if t == state.turn => win.push((outcome.plies, m)),
+                _ => lose.push((outcome.plies, m)),
             }
         }
 
-        let mut rng = thread_rng();
-        if let m @ Some(_) = win.choose(&mut rng) {
+        // Win fastest, lose slowest.
+        let min_win_plies = win.iter().map(|(p, _)| *p).min();
+        let win = win
+            .into_iter()
+            .filter(|(p, _)| Some(*p) == min_win_plies)
+            .map(|(_, m)| m)
+            .collect::<Vec<_>>();
+        let max_lose_plies = lose.iter().map(|(p, _)| *p).max();
+        let lose = lose
+            .into_iter()
+            .filter(|(p, _)| Some(*p) == max_lose_plies)
+            .map(|(_, m)| m)
+            .collect::<Vec<_>>();
+
+        if let m @ Some(_) = win.choose(rng) {
             m.cloned()
-        } else if let m @ Some(_) = draw.choose(&mut rng) {
+        } else if let m @ Some(_) = draw.choose(rng) {
             m.cloned()
         } else {
-            lose.choose(&mut rng).cloned()
+            lose.choose(rng).cloned()
         }
     }
 }
 
+/// Outcomes of solved states under given rules.
 pub struct OptimalCache {
-    states: OptimalWinningStates,
+    rules: Rules,
+    tablebase: Tablebase,
+    /// Number of threads solving new states.
+    threads: usize,
 }
 
-const OPTIMAL_SERIALIZATION_ORDER: [Option<Turn>; 3] =
-    [Some(Turn::Player), Some(Turn::Opponent), None];
-
 impl OptimalCache {
-    pub fn new() -> Self {
+    pub fn new(rules: Rules) -> Self {
         Self {
-            states: OptimalWinningStates::new(),
+            rules,
+            tablebase: Tablebase::new(&rules),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
         }
     }
 
+    /// Memory-maps the cache file, so states are read from it only when looked up
+    /// and processes using the same file share it in memory.
+    ///
+    /// Unlike `load_from_disk`, the checksum is not checked, as it would read the whole file.
+    /// The file must not be modified while mapped (`save_to_disk` does not, it replaces it).
+    /// Once any state is added, the cache is copied to memory.
+    pub fn open(rules: Rules, path: impl AsRef<Path>) -> Result<Self, String> {
+        let file = File::open(path.as_ref()).map_err(|e| e.to_string())?;
+        // SAFETY: the file is not modified while mapped, see above.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| e.to_string())?;
+        let header = cache_file::decode_header(&mmap, &rules)?;
+        Ok(Self {
+            tablebase: Tablebase::from_mmap(
+                mmap,
+                cache_file::HEADER_LEN,
+                header.with_plies,
+                &rules,
+            )?,
+            ..Self::new(rules)
+        })
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Whether states are read from a memory-mapped file.
+    pub fn is_mapped(&self) -> bool {
+        self.tablebase.is_mapped()
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Defaults to the available parallelism.
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "At least one thread is needed");
+        self.threads = threads;
+    }
+
+    /// Adds states from the file to the cache.
+    /// Fails on files of other format versions or rules, with wrong checksum,
+    /// or with states conflicting with the cache.
     pub fn load_from_disk(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
         let cache = fs::read(path.as_ref()).map_err(|e| e.to_string())?;
-        if cache.len() % 4 != 0 {
-            return Err("Malformed cache (expected sequence of 32-bit values).".to_string());
-        }
+        self.merge_bytes(&cache)
+    }
 
-        let mut remaining;
-        let mut it = cache.as_slice().chunks_exact(4).map(|chunk| {
-            let arr = chunk.try_into().unwrap();
-            u32::from_le_bytes(arr)
-        });
-
-        for t in &OPTIMAL_SERIALIZATION_ORDER {
-            remaining = it.next().ok_or_else(|| "Expected a number".to_string())?;
-            for _ in 0..remaining {
-                let num = it.next().ok_or_else(|| "Expected a number".to_string())?;
-                let state = unsafe { std::mem::transmute(num) }; // TODO: might be done without unsafe
-                self.states.entry(*t).or_default().insert(state);
-            }
-        }
+    /// Fails on conflicting states, see `merge`.
+    fn merge_bytes(&mut self, cache: &[u8]) -> Result<(), String> {
+        let (tablebase, with_plies) = cache_file::decode(cache, &self.rules)?;
+        let other = Self {
+            tablebase: Tablebase::from_bytes(tablebase, with_plies, &self.rules)?,
+            ..Self::new(self.rules)
+        };
+        self.merge(&other).map(|_| ())
+    }
 
-        if !matches!(it.next(), None) {
-            return Err("Unknown trailing data".to_string());
+    /// Replaces the file, see `cache_file` for the layout.
+    /// States saved to it meanwhile by other processes are lost, unlike with `merge_to_disk`.
+    pub fn save_to_disk(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let _lock = cache_file::lock(path.as_ref())?;
+        self.write_to_disk(path.as_ref())
+    }
+
+    /// Adds states from the file, if there is any, to the cache and saves the union.
+    /// Meant for several processes sharing one file, as it is locked meanwhile.
+    pub fn merge_to_disk(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let _lock = cache_file::lock(path.as_ref())?;
+        match fs::read(path.as_ref()) {
+            Ok(cache) => self.merge_bytes(&cache)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.to_string()),
         }
+        self.write_to_disk(path.as_ref())
+    }
 
-        Ok(())
+    fn write_to_disk(&self, path: &Path) -> Result<(), String> {
+        let buf = cache_file::encode(
+            &self.rules,
+            self.tablebase.to_bytes(),
+            self.tablebase.has_plies(),
+        );
+        cache_file::write_atomically(path, &buf)
     }
 
-    pub fn save_to_disk(&self, path: impl AsRef<Path>) -> Result<(), String> {
-        let mut buf = vec![];
-
-        let empty_hashset = HashSet::new();
-        let mut write_u32_to_buf = |num: u32| buf.extend_from_slice(&num.to_le_bytes());
-        for t in &OPTIMAL_SERIALIZATION_ORDER {
-            let elems = self.states.get(t).unwrap_or(&empty_hashset);
-            write_u32_to_buf(elems.len().try_into().unwrap());
-            for e in elems {
-                let num: u32 = unsafe { std::mem::transmute(*e) };
-                write_u32_to_buf(num);
-            }
-        }
+    pub fn len(&self) -> usize {
+        self.tablebase.len()
+    }
 
-        fs::write(path.as_ref(), &buf).map_err(|e| e.to_string())?;
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-        Ok(())
+    /// Whether plies to the end are kept besides who wins, as needed for optimal play
+    /// and solving. New caches have them.
+    pub fn has_plies(&self) -> bool {
+        self.tablebase.has_plies()
     }
 
-    pub fn len(&self) -> usize {
-        let mut len = 0;
-        for (_, v) in self.states.iter() {
-            len += v.len();
-        }
-        len
+    /// Keeps only who wins, 2 bits per state, e.g. to share a small cache for lookups
+    /// by `get_state_winningness`. Merging with such a cache drops the plies too.
+    pub fn drop_plies(&mut self) {
+        self.tablebase.drop_plies();
     }
 
     // first option - if in cache
     // second option - None - draw, Some(t) - t wins
-    fn get_state_winningness(&self, state: State) -> Option<Option<Turn>> {
-        for t in [Some(Turn::Player), Some(Turn::Opponent), None] {
-            if self
-                .states
-                .get(&t)
-                .map_or(false, |col| col.contains(&state))
-            {
-                return Some(t);
-            }
-        }
+    pub fn get_state_winningness(&self, state: State) -> Option<Option<Turn>> {
+        self.tablebase.winner(state.index(&self.rules))
+    }
+
+    /// `None` if not in cache, or the cache has no plies.
+    pub fn get_state_outcome(&self, state: State) -> Option<Outcome> {
+        self.tablebase.get(state.index(&self.rules))
+    }
 
-        None
+    fn insert(&mut self, state: State, winner: Option<Turn>, plies: u16) {
+        self.tablebase
+            .set(state.index(&self.rules), Outcome { winner, plies });
     }
 }
@@ -0,0 +1,229 @@
+//! Layout of the cache file: a header followed by the tablebase (verdicts of all states,
+//! optionally followed by their plies, see `Tablebase`).
+//!
+//! All numbers are little-endian. The header consists of
+//! - magic `PANCACHE`,
+//! - format version (`u32`),
+//! - fingerprint of the rules (4 bytes, see `Rules::fingerprint`),
+//! - number of all states under the rules (`u32`),
+//! - flags (`u32`), bit 0 set if the plies follow the verdicts,
+//! - FNV-1a checksum of the tablebase (`u64`).
+//!
+//! Files are replaced atomically, and writers take an advisory lock on `<file>.lock`.
+
+use crate::{rules::Rules, state::State};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+const MAGIC: &[u8; 8] = b"PANCACHE";
+/// To be increased with every change of the layout, including the encoding of `State`.
+const VERSION: u32 = 2;
+pub(super) const HEADER_LEN: usize = 8 + 4 + 4 + 4 + 4 + 8;
+const WITH_PLIES: u32 = 1;
+
+/// What the header tells about the tablebase following it.
+pub(super) struct Header {
+    pub with_plies: bool,
+    pub checksum: u64,
+}
+
+/// Header followed by the tablebase.
+pub(super) fn encode(rules: &Rules, tablebase: &[u8], with_plies: bool) -> Vec<u8> {
+    let states_cnt = u32::try_from(State::count(rules)).unwrap();
+    let flags = if with_plies { WITH_PLIES } else { 0 };
+    let mut buf = Vec::with_capacity(HEADER_LEN + tablebase.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&rules.fingerprint());
+    buf.extend_from_slice(&states_cnt.to_le_bytes());
+    buf.extend_from_slice(&flags.to_le_bytes());
+    buf.extend_from_slice(&checksum(tablebase).to_le_bytes());
+    buf.extend_from_slice(tablebase);
+    buf
+}
+
+/// Checks the header against the rules and returns the tablebase, and whether it has plies.
+pub(super) fn decode<'b>(bytes: &'b [u8], rules: &Rules) -> Result<(&'b [u8], bool), String> {
+    let header = decode_header(bytes, rules)?;
+    let tablebase = &bytes[HEADER_LEN..];
+    if checksum(tablebase) != header.checksum {
+        return Err("Corrupted cache (checksum mismatch).".to_string());
+    }
+
+    Ok((tablebase, header.with_plies))
+}
+
+/// Checks the header against the rules without reading the tablebase.
+pub(super) fn decode_header(bytes: &[u8], rules: &Rules) -> Result<Header, String> {
+    let header = bytes
+        .get(..HEADER_LEN)
+        .ok_or_else(|| "Not a cache file (too short).".to_string())?;
+    let (magic, header) = header.split_at(MAGIC.len());
+    let (version, header) = header.split_at(4);
+    let (fingerprint, header) = header.split_at(4);
+    let (states_cnt, header) = header.split_at(4);
+    let (flags, checksum_bytes) = header.split_at(4);
+
+    if magic != MAGIC {
+        return Err("Not a cache file (wrong magic number).".to_string());
+    }
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    if version != VERSION {
+        return Err(format!(
+            "Unsupported cache format version {} (expected {}).",
+            version, VERSION
+        ));
+    }
+    if fingerprint != rules.fingerprint() {
+        return Err(format!(
+            "Cache built for different rules (fingerprint {:02x?}, expected {:02x?}).",
+            fingerprint,
+            rules.fingerprint()
+        ));
+    }
+    let states_cnt = u32::from_le_bytes(states_cnt.try_into().unwrap());
+    if states_cnt as usize != State::count(rules) {
+        return Err(format!(
+            "Cache for {} states, expected {}.",
+            states_cnt,
+            State::count(rules)
+        ));
+    }
+
+    let flags = u32::from_le_bytes(flags.try_into().unwrap());
+    if flags & !WITH_PLIES != 0 {
+        return Err(format!("Unknown cache flags {:#x}.", flags));
+    }
+
+    Ok(Header {
+        with_plies: flags & WITH_PLIES != 0,
+        checksum: u64::from_le_bytes(checksum_bytes.try_into().unwrap()),
+    })
+}
+
+/// 64-bit FNV-1a.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `path` with `suffix` appended to the file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Blocks until no other process writes the file; the lock is held until the result is dropped.
+pub(super) fn lock(path: &Path) -> Result<File, String> {
+    let lock_path = with_suffix(path, ".lock");
+    let file = File::create(&lock_path).map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+    file.lock()
+        .map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+    Ok(file)
+}
+
+/// Writes to a temporary file renamed over `path`, so it is never left half-written,
+/// and memory maps of the old file stay valid.
+pub(super) fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp_path = with_suffix(path, &format!(".{}.tmp", process::id()));
+    let write = || -> io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLEBASE: &[u8] = &[1, 2, 3, 4, 5];
+
+    fn encoded() -> Vec<u8> {
+        encode(&Rules::STANDARD, TABLEBASE, true)
+    }
+
+    fn decode_err(bytes: &[u8]) -> String {
+        decode(bytes, &Rules::STANDARD).unwrap_err()
+    }
+
+    #[test]
+    fn decode_inverts_encode() {
+        for with_plies in [true, false] {
+            let bytes = encode(&Rules::STANDARD, TABLEBASE, with_plies);
+            assert_eq!(bytes.len(), HEADER_LEN + TABLEBASE.len());
+            assert_eq!(
+                decode(&bytes, &Rules::STANDARD),
+                Ok((TABLEBASE, with_plies))
+            );
+        }
+    }
+
+    #[test]
+    fn decode_rejects_wrong_header() {
+        assert_eq!(
+            decode_err(&encoded()[..10]),
+            "Not a cache file (too short)."
+        );
+
+        let mut bytes = encoded();
+        bytes[0] = b'X';
+        assert_eq!(decode_err(&bytes), "Not a cache file (wrong magic number).");
+
+        let mut bytes = encoded();
+        bytes[8..12].copy_from_slice(&7u32.to_le_bytes());
+        assert_eq!(
+            decode_err(&bytes),
+            format!("Unsupported cache format version 7 (expected {}).", VERSION)
+        );
+
+        let other_rules = Rules {
+            cards_cnt_when_taking_from_stack: 1,
+            ..Rules::STANDARD
+        };
+        assert_eq!(
+            decode_err(&encode(&other_rules, TABLEBASE, true)),
+            "Cache built for different rules (fingerprint [01, 00, 01, 00], \
+             expected [03, 00, 01, 00])."
+        );
+
+        let mut bytes = encoded();
+        bytes[16..20].copy_from_slice(&5u32.to_le_bytes());
+        assert_eq!(
+            decode_err(&bytes),
+            format!(
+                "Cache for 5 states, expected {}.",
+                State::count(&Rules::STANDARD)
+            )
+        );
+
+        let mut bytes = encoded();
+        bytes[20..24].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(decode_err(&bytes), "Unknown cache flags 0x3.");
+    }
+
+    #[test]
+    fn decode_rejects_corrupted_tablebase() {
+        let mut bytes = encoded();
+        *bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(decode_err(&bytes), "Corrupted cache (checksum mismatch).");
+
+        let mut bytes = encoded();
+        bytes.pop();
+        assert_eq!(decode_err(&bytes), "Corrupted cache (checksum mismatch).");
+        // The header alone is checked without reading the tablebase.
+        assert!(decode_header(&bytes, &Rules::STANDARD).is_ok());
+    }
+}
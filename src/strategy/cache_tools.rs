@@ -0,0 +1,372 @@
+use crate::{
+    state::{State, Turn, VerboseState},
+    strategy::{solver::par_flat_map, OptimalCache, Outcome},
+};
+use std::fmt;
+
+/// Known states with given side to move, by their outcome for it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct OutcomeCounts {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl OutcomeCounts {
+    pub fn total(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CacheStats {
+    /// All states under the rules, known or not.
+    pub states_cnt: usize,
+    pub player_to_move: OutcomeCounts,
+    pub opponent_to_move: OutcomeCounts,
+    /// Of the longest decided game, `None` if the cache has no plies.
+    pub max_plies: Option<u16>,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let known = self.player_to_move.total() + self.opponent_to_move.total();
+        writeln!(
+            f,
+            "Known states: {} of {} ({:.2}%)",
+            known,
+            self.states_cnt,
+            100.0 * known as f64 / self.states_cnt as f64
+        )?;
+        for (turn, counts) in [
+            (Turn::Player, self.player_to_move),
+            (Turn::Opponent, self.opponent_to_move),
+        ] {
+            writeln!(
+                f,
+                "{:?} to move: {} wins, {} losses, {} draws",
+                turn, counts.wins, counts.losses, counts.draws
+            )?;
+        }
+        match self.max_plies {
+            Some(plies) => write!(f, "Longest decided game: {} plies", plies),
+            None => write!(f, "Longest decided game: unknown (no plies cached)"),
+        }
+    }
+}
+
+/// Comparison of two caches of the same rules.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct CacheDiff {
+    pub same_cnt: usize,
+    pub only_left_cnt: usize,
+    pub only_right_cnt: usize,
+    /// States with different outcomes: at least one of the caches is corrupted.
+    pub conflicts: Vec<(VerboseState, Outcome, Outcome)>,
+}
+
+impl fmt::Display for CacheDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Same: {}", self.same_cnt)?;
+        writeln!(f, "Only in left: {}", self.only_left_cnt)?;
+        writeln!(f, "Only in right: {}", self.only_right_cnt)?;
+        write!(f, "Conflicting: {}", self.conflicts.len())?;
+        for (s, left, right) in &self.conflicts {
+            write!(f, "\n  {}: {:?} vs {:?}", s, left, right)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cached state whose outcome does not follow from the outcomes of its following states.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Inconsistency {
+    pub state: VerboseState,
+    pub outcome: Outcome,
+    /// `None` if some following state is not cached.
+    pub expected: Option<Outcome>,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}, ", self.state, self.outcome)?;
+        match self.expected {
+            Some(expected) => write!(f, "expected {:?}", expected),
+            None => write!(f, "some following state is not cached"),
+        }
+    }
+}
+
+impl OptimalCache {
+    /// Known states with their outcomes, in order of `State::index`.
+    /// Plies are 0 in caches without them.
+    pub fn iter(&self) -> impl Iterator<Item = (State, Outcome)> + '_ {
+        (0..self.tablebase.states_cnt()).filter_map(|idx| {
+            let outcome = self.known_outcome(idx)?;
+            Some((State::from_index(idx, &self.rules), outcome))
+        })
+    }
+
+    /// Like `Tablebase::get`, with 0 plies in caches without them.
+    fn known_outcome(&self, idx: usize) -> Option<Outcome> {
+        let winner = self.tablebase.winner(idx)?;
+        Some(
+            self.tablebase
+                .get(idx)
+                .unwrap_or(Outcome { winner, plies: 0 }),
+        )
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            states_cnt: self.tablebase.states_cnt(),
+            player_to_move: OutcomeCounts::default(),
+            opponent_to_move: OutcomeCounts::default(),
+            max_plies: self.has_plies().then_some(0),
+        };
+        for (s, outcome) in self.iter() {
+            let counts = match s.turn() {
+                Turn::Player => &mut stats.player_to_move,
+                Turn::Opponent => &mut stats.opponent_to_move,
+            };
+            match outcome.winner {
+                Some(t) if t == s.turn() => counts.wins += 1,
+                Some(_) => counts.losses += 1,
+                None => counts.draws += 1,
+            }
+            stats.max_plies = stats.max_plies.map(|p| p.max(outcome.plies));
+        }
+        stats
+    }
+
+    /// Plies are compared only if both caches have them.
+    pub fn diff(&self, other: &OptimalCache) -> Result<CacheDiff, String> {
+        if self.rules != other.rules {
+            return Err("Caches of different rules".to_string());
+        }
+
+        let compare_plies = self.has_plies() && other.has_plies();
+        let mut diff = CacheDiff::default();
+        for idx in 0..self.tablebase.states_cnt() {
+            match (self.known_outcome(idx), other.known_outcome(idx)) {
+                (Some(left), Some(right))
+                    if left.winner == right.winner
+                        && (!compare_plies || left.plies == right.plies) =>
+                {
+                    diff.same_cnt += 1
+                }
+                (Some(left), Some(right)) => {
+                    let s = State::from_index(idx, &self.rules);
+                    let vs = VerboseState::from_state(s, &self.rules);
+                    diff.conflicts.push((vs, left, right));
+                }
+                (Some(_), None) => diff.only_left_cnt += 1,
+                (None, Some(_)) => diff.only_right_cnt += 1,
+                (None, None) => {}
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Adds states known to the other cache, returning how many were added.
+    /// Fails without changing anything if the caches conflict.
+    /// Plies are kept only if both caches have them.
+    pub fn merge(&mut self, other: &OptimalCache) -> Result<usize, String> {
+        let diff = self.diff(other)?;
+        if let Some((s, left, right)) = diff.conflicts.first() {
+            return Err(format!(
+                "Corrupted cache: {} conflicting states, e.g. {}: {:?} vs {:?}",
+                diff.conflicts.len(),
+                s,
+                left,
+                right
+            ));
+        }
+
+        let keep_plies = self.has_plies() && other.has_plies();
+        if self.is_empty() {
+            self.tablebase = other.tablebase.clone();
+        } else {
+            for idx in 0..other.tablebase.states_cnt() {
+                if let Some(outcome) = other.known_outcome(idx) {
+                    if self.tablebase.winner(idx).is_none() {
+                        self.tablebase.set(idx, outcome);
+                    }
+                }
+            }
+        }
+        if !keep_plies {
+            self.drop_plies();
+        }
+        Ok(diff.only_right_cnt)
+    }
+
+    /// Checks every cached state against its following states: a win for the mover needs
+    /// a following state won by it, a loss needs all of them lost, a draw neither.
+    /// Plies to the end are checked too, if cached. Invalid states (see `VerboseState::validate`) are
+    /// skipped: they cannot occur in a game, so their outcomes do not matter.
+    pub fn verify(&self) -> Vec<Inconsistency> {
+        par_flat_map(self.tablebase.states_cnt(), self.threads, |idx, out| {
+            let Some(outcome) = self.known_outcome(idx) else {
+                return;
+            };
+            let state = State::from_index(idx, &self.rules);
+            if VerboseState::from_state(state, &self.rules)
+                .validate(&self.rules)
+                .is_err()
+            {
+                return;
+            }
+            let expected = self.expected_outcome(state);
+            let consistent = expected.is_some_and(|e| {
+                e.winner == outcome.winner && (!self.has_plies() || e.plies == outcome.plies)
+            });
+            if !consistent {
+                out.push(Inconsistency {
+                    state: VerboseState::from_state(state, &self.rules),
+                    outcome,
+                    expected,
+                });
+            }
+        })
+    }
+
+    /// Outcome following from the cached outcomes of following states.
+    fn expected_outcome(&self, state: State) -> Option<Outcome> {
+        let vs = VerboseState::from_state(state, &self.rules);
+        if let Some(winner) = vs.winner() {
+            return Some(Outcome {
+                winner: Some(winner),
+                plies: 0,
+            });
+        }
+
+        let following_states = vs.following_states(&self.rules);
+        let mut win_plies = None::<u16>;
+        let mut loss_plies = Some(0);
+        for next in &following_states {
+            let next = State::from_verbose(next, &self.rules).unwrap();
+            let outcome = self.known_outcome(next.index(&self.rules))?;
+            match outcome.winner {
+                Some(t) if t == vs.turn => {
+                    win_plies = Some(win_plies.map_or(outcome.plies, |p| p.min(outcome.plies)));
+                }
+                Some(_) => loss_plies = loss_plies.map(|p| p.max(outcome.plies)),
+                None => loss_plies = None,
+            }
+        }
+
+        let decided = |winner, plies: u16| Outcome {
+            winner: Some(winner),
+            plies: plies + 1,
+        };
+        Some(match (win_plies, loss_plies) {
+            (Some(plies), _) => decided(vs.turn, plies),
+            (None, Some(plies)) if !following_states.is_empty() => decided(vs.turn.next(), plies),
+            _ => Outcome {
+                winner: None,
+                plies: 0,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rules::Rules, strategy::Optimal};
+
+    /// Won by putting the nine, so only it and the finished state are reachable.
+    const START: &str = "9|-|991111JJJJQQQQKKKKAAAA p";
+
+    /// Cache of the states reachable from `START`, with the outcome of `START`.
+    fn solved() -> (OptimalCache, State, Outcome) {
+        let rules = Rules::STANDARD;
+        let mut cache = OptimalCache::new(rules);
+        let start = VerboseState::parse(START, &rules).unwrap();
+        Optimal::new_with_mut_cache(&start, &mut cache);
+        let s = State::from_verbose(&start, &rules).unwrap();
+        let outcome = cache.get_state_outcome(s).unwrap();
+        (cache, s, outcome)
+    }
+
+    fn corrupt(cache: &mut OptimalCache, s: State, outcome: Outcome) -> Outcome {
+        let corrupted = Outcome {
+            plies: outcome.plies + 2,
+            ..outcome
+        };
+        cache.insert(s, corrupted.winner, corrupted.plies);
+        corrupted
+    }
+
+    #[test]
+    fn verify_reports_corrupted_outcome() {
+        let (mut cache, s, outcome) = solved();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            outcome,
+            Outcome {
+                winner: Some(Turn::Player),
+                plies: 1
+            }
+        );
+        assert_eq!(cache.verify(), vec![]);
+
+        let corrupted = corrupt(&mut cache, s, outcome);
+        assert_eq!(
+            cache.verify(),
+            vec![Inconsistency {
+                state: START.parse().unwrap(),
+                outcome: corrupted,
+                expected: Some(outcome),
+            }]
+        );
+    }
+
+    #[test]
+    fn merge_rejects_conflicts() {
+        let (mut cache, s, outcome) = solved();
+        let mut empty = OptimalCache::new(Rules::STANDARD);
+        assert_eq!(empty.merge(&cache), Ok(cache.len()));
+        assert_eq!(cache.merge(&empty), Ok(0));
+
+        let mut other = solved().0;
+        let corrupted = corrupt(&mut other, s, outcome);
+        let diff = cache.diff(&other).unwrap();
+        assert_eq!(diff.same_cnt, cache.len() - 1);
+        assert_eq!(
+            diff.conflicts,
+            vec![(START.parse().unwrap(), outcome, corrupted)]
+        );
+
+        let len = cache.len();
+        let err = cache.merge(&other).unwrap_err();
+        assert!(
+            err.starts_with("Corrupted cache: 1 conflicting states"),
+            "{}",
+            err
+        );
+        assert_eq!(cache.len(), len);
+        assert_eq!(cache.get_state_outcome(s), Some(outcome));
+    }
+
+    #[test]
+    fn caches_without_plies_keep_winners() {
+        let (mut cache, s, outcome) = solved();
+        let mut verdicts = OptimalCache::new(Rules::STANDARD);
+        verdicts.merge(&cache).unwrap();
+        verdicts.drop_plies();
+        assert!(!verdicts.has_plies());
+        assert_eq!(verdicts.len(), 2);
+        assert_eq!(verdicts.get_state_winningness(s), Some(outcome.winner));
+        assert_eq!(verdicts.get_state_outcome(s), None);
+        assert_eq!(verdicts.stats().max_plies, None);
+        assert_eq!(verdicts.verify(), vec![]);
+        assert_eq!(cache.diff(&verdicts).unwrap().same_cnt, 2);
+
+        // Merged states have no plies, so none are kept.
+        assert_eq!(cache.merge(&verdicts), Ok(0));
+        assert!(!cache.has_plies());
+        assert_eq!(cache.get_state_winningness(s), Some(outcome.winner));
+    }
+}
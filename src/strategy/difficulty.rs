@@ -0,0 +1,129 @@
+use crate::{
+    rules::Rules,
+    state::{Move, Turn, VerboseState},
+    strategy::{CardsInHand, Evaluation, Optimal, Random, Strategy, WeakCards},
+};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::{fmt, str::FromStr};
+
+/// Plays the move leaving its own hand best, without looking ahead.
+pub struct Greedy {
+    pub rules: Rules,
+}
+
+impl Strategy for Greedy {
+    fn get_next_move(&self, state: &VerboseState, _rng: &mut dyn RngCore) -> Option<Move> {
+        let evaluation = (WeakCards { weight: 1 }, CardsInHand { weight: 1 });
+        let score = |m: &Move| {
+            let score = evaluation.evaluate(&m.state, &self.rules);
+            match state.turn {
+                Turn::Player => score,
+                Turn::Opponent => -score,
+            }
+        };
+        state
+            .possible_moves(&self.rules)
+            .into_iter()
+            .max_by_key(score)
+    }
+}
+
+/// Plays optimally, except for blundering with given probability:
+/// then it plays a move with worse outcome than the optimal one, if there is any.
+pub struct EpsilonOptimal<'a> {
+    pub optimal: Optimal<'a>,
+    pub blunder_probability: f64,
+}
+
+impl<'a> Strategy for EpsilonOptimal<'a> {
+    fn get_next_move(&self, state: &VerboseState, rng: &mut dyn RngCore) -> Option<Move> {
+        let best = self.optimal.get_next_move(state, rng)?;
+        if !rng.gen_bool(self.blunder_probability) {
+            return Some(best);
+        }
+
+        // Win > draw > loss, for the side to move.
+        let rank = |m: &Move| match self.optimal.get_winning_turn(&m.state) {
+            Some(t) if t == state.turn => 2,
+            None => 1,
+            Some(_) => 0,
+        };
+        let best_rank = rank(&best);
+        let blunders = state
+            .possible_moves(self.optimal.rules())
+            .into_iter()
+            .filter(|m| rank(m) < best_rank)
+            .collect::<Vec<_>>();
+        Some(blunders.choose(rng).cloned().unwrap_or(best))
+    }
+}
+
+/// Strength of the computer opponent.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Difficulty {
+    Random,
+    Greedy,
+    EpsilonOptimal {
+        blunder_probability: f64,
+    },
+    #[default]
+    Optimal,
+}
+
+impl Difficulty {
+    pub const DEFAULT_BLUNDER_PROBABILITY: f64 = 0.2;
+
+    pub fn strategy<'a>(self, optimal: Optimal<'a>) -> Box<dyn Strategy + 'a> {
+        let rules = *optimal.rules();
+        match self {
+            Difficulty::Random => Box::new(Random { rules }),
+            Difficulty::Greedy => Box::new(Greedy { rules }),
+            Difficulty::EpsilonOptimal {
+                blunder_probability,
+            } => Box::new(EpsilonOptimal {
+                optimal,
+                blunder_probability,
+            }),
+            Difficulty::Optimal => Box::new(optimal),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Random => write!(f, "random"),
+            Difficulty::Greedy => write!(f, "greedy"),
+            Difficulty::EpsilonOptimal {
+                blunder_probability,
+            } => write!(f, "epsilon:{}", blunder_probability),
+            Difficulty::Optimal => write!(f, "optimal"),
+        }
+    }
+}
+
+/// Accepts the `Display` format; `epsilon` alone uses the default blunder probability.
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "random" => Ok(Difficulty::Random),
+            None if s == "greedy" => Ok(Difficulty::Greedy),
+            None if s == "epsilon" => Ok(Difficulty::EpsilonOptimal {
+                blunder_probability: Self::DEFAULT_BLUNDER_PROBABILITY,
+            }),
+            None if s == "optimal" => Ok(Difficulty::Optimal),
+            Some(("epsilon", p)) => match p.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(Difficulty::EpsilonOptimal {
+                    blunder_probability: p,
+                }),
+                _ => Err(format!("Invalid blunder probability '{}'", p)),
+            },
+            _ => Err(format!(
+                "Unknown difficulty '{}', expected random, greedy, epsilon[:P] or optimal",
+                s
+            )),
+        }
+    }
+}
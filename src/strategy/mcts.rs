@@ -0,0 +1,235 @@
+use crate::{
+    multi::{MultiMove, MultiState, MultiStrategy},
+    rules::Rules,
+    state::{Move, MoveDescription, Turn, VerboseState},
+    strategy::Strategy,
+};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+pub enum MctsBudget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+/// Monte Carlo Tree Search with UCT selection and random playouts.
+///
+/// Needs no precomputation, so it works for two players as well as for more of them.
+pub struct Mcts {
+    pub rules: Rules,
+    pub budget: MctsBudget,
+    /// Weight of exploration in UCT; `2.0.sqrt()` is the textbook value.
+    pub exploration: f64,
+    /// Playouts not finished within this number of plies are scored as draws.
+    pub max_playout_len: usize,
+}
+
+impl Mcts {
+    pub fn new(rules: Rules, budget: MctsBudget) -> Self {
+        Self {
+            rules,
+            budget,
+            exploration: 2f64.sqrt(),
+            max_playout_len: 200,
+        }
+    }
+
+    fn search<S: SearchState>(&self, root: &S, rng: &mut dyn RngCore) -> Option<MoveDescription> {
+        let mut tree = vec![Node::new(root.clone(), None, None, &self.rules)];
+        if tree[0].untried.is_empty() {
+            return None;
+        }
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let budget_left = match self.budget {
+                MctsBudget::Iterations(cnt) => iterations < cnt,
+                MctsBudget::Time(limit) => iterations == 0 || start.elapsed() < limit,
+            };
+            if !budget_left {
+                break;
+            }
+            iterations += 1;
+
+            // Selection
+            let mut idx = 0;
+            while tree[idx].untried.is_empty() && !tree[idx].children.is_empty() {
+                idx = self.select_child(&tree, idx);
+            }
+
+            // Expansion
+            if !tree[idx].untried.is_empty() {
+                let pick = rng.gen_range(0..tree[idx].untried.len());
+                let (desc, state) = tree[idx].untried.swap_remove(pick);
+                let child = Node::new(state, Some(idx), Some(desc), &self.rules);
+                tree.push(child);
+                let child_idx = tree.len() - 1;
+                tree[idx].children.push(child_idx);
+                idx = child_idx;
+            }
+
+            // Simulation
+            let rewards = self.playout(&tree[idx].state, rng);
+
+            // Backpropagation
+            let mut cur = Some(idx);
+            while let Some(i) = cur {
+                let node = &mut tree[i];
+                node.visits += 1;
+                for (total, r) in node.rewards.iter_mut().zip(&rewards) {
+                    *total += r;
+                }
+                cur = node.parent;
+            }
+        }
+
+        tree[0]
+            .children
+            .iter()
+            .max_by_key(|&&i| tree[i].visits)
+            .and_then(|&i| tree[i].desc)
+    }
+
+    fn select_child<S: SearchState>(&self, tree: &[Node<S>], idx: usize) -> usize {
+        let parent = &tree[idx];
+        let mover = parent.state.player_to_move();
+        let ln_visits = (parent.visits as f64).ln();
+        let uct = |i: usize| {
+            let child = &tree[i];
+            let visits = child.visits as f64;
+            child.rewards[mover] / visits + self.exploration * (ln_visits / visits).sqrt()
+        };
+        *parent
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+            .unwrap()
+    }
+
+    fn playout<S: SearchState>(&self, state: &S, rng: &mut dyn RngCore) -> Vec<f64> {
+        let mut state = state.clone();
+        for _ in 0..self.max_playout_len {
+            if let Some(rewards) = state.rewards() {
+                return rewards;
+            }
+            state = state
+                .following(&self.rules)
+                .choose(rng)
+                .expect("game not finished")
+                .1
+                .clone();
+        }
+        state
+            .rewards()
+            .unwrap_or_else(|| vec![0.5; state.players_cnt()])
+    }
+}
+
+impl Strategy for Mcts {
+    fn get_next_move(&self, state: &VerboseState, rng: &mut dyn RngCore) -> Option<Move> {
+        let desc = self.search(state, rng)?;
+        state
+            .possible_moves(&self.rules)
+            .into_iter()
+            .find(|m| m.desc == desc)
+    }
+}
+
+impl MultiStrategy for Mcts {
+    fn get_next_move(&self, state: &MultiState, rng: &mut dyn RngCore) -> Option<MultiMove> {
+        let desc = self.search(state, rng)?;
+        state
+            .possible_moves(&self.rules)
+            .into_iter()
+            .find(|m| m.desc == desc)
+    }
+}
+
+struct Node<S> {
+    state: S,
+    parent: Option<usize>,
+    /// Move leading to this node.
+    desc: Option<MoveDescription>,
+    children: Vec<usize>,
+    untried: Vec<(MoveDescription, S)>,
+    visits: usize,
+    /// Sum of playout rewards, for each player.
+    rewards: Vec<f64>,
+}
+
+impl<S: SearchState> Node<S> {
+    fn new(state: S, parent: Option<usize>, desc: Option<MoveDescription>, rules: &Rules) -> Self {
+        Self {
+            untried: state.following(rules),
+            rewards: vec![0.0; state.players_cnt()],
+            state,
+            parent,
+            desc,
+            children: vec![],
+            visits: 0,
+        }
+    }
+}
+
+trait SearchState: Clone {
+    fn following(&self, rules: &Rules) -> Vec<(MoveDescription, Self)>;
+    fn players_cnt(&self) -> usize;
+    fn player_to_move(&self) -> usize;
+    /// Reward of each player in `[0, 1]` once the game is finished.
+    fn rewards(&self) -> Option<Vec<f64>>;
+}
+
+impl SearchState for VerboseState {
+    fn following(&self, rules: &Rules) -> Vec<(MoveDescription, Self)> {
+        self.possible_moves(rules)
+            .into_iter()
+            .map(|m| (m.desc, m.state))
+            .collect()
+    }
+
+    fn players_cnt(&self) -> usize {
+        2
+    }
+
+    fn player_to_move(&self) -> usize {
+        match self.turn {
+            Turn::Player => 0,
+            Turn::Opponent => 1,
+        }
+    }
+
+    fn rewards(&self) -> Option<Vec<f64>> {
+        match self.winner()? {
+            Turn::Player => Some(vec![1.0, 0.0]),
+            Turn::Opponent => Some(vec![0.0, 1.0]),
+        }
+    }
+}
+
+impl SearchState for MultiState {
+    fn following(&self, rules: &Rules) -> Vec<(MoveDescription, Self)> {
+        self.possible_moves(rules)
+            .into_iter()
+            .map(|m| (m.desc, m.state))
+            .collect()
+    }
+
+    fn players_cnt(&self) -> usize {
+        MultiState::players_cnt(self)
+    }
+
+    fn player_to_move(&self) -> usize {
+        self.turn
+    }
+
+    fn rewards(&self) -> Option<Vec<f64>> {
+        let loser = self.loser()?;
+        Some(
+            (0..self.players_cnt())
+                .map(|p| if p == loser { 0.0 } else { 1.0 })
+                .collect(),
+        )
+    }
+}
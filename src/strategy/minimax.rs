@@ -0,0 +1,266 @@
+use crate::{
+    rules::Rules,
+    state::{CardsHand, Move, MoveDescription, State, Turn, VerboseState},
+    strategy::Strategy,
+};
+use rand::RngCore;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Heuristic value of a state which is not finished.
+pub trait Evaluation {
+    /// Positive values are good for `Turn::Player`, negative ones for `Turn::Opponent`.
+    fn evaluate(&self, state: &VerboseState, rules: &Rules) -> i32;
+}
+
+/// Sum of two evaluations.
+impl<A: Evaluation, B: Evaluation> Evaluation for (A, B) {
+    fn evaluate(&self, state: &VerboseState, rules: &Rules) -> i32 {
+        self.0.evaluate(state, rules) + self.1.evaluate(state, rules)
+    }
+}
+
+/// The fewer cards in hand, the better.
+pub struct CardsInHand {
+    pub weight: i32,
+}
+
+impl Evaluation for CardsInHand {
+    fn evaluate(&self, state: &VerboseState, _rules: &Rules) -> i32 {
+        let diff = state.opponent_hand.len() as i32 - state.player_hand.len() as i32;
+        self.weight * diff
+    }
+}
+
+/// Weak cards are hard to get rid of, as they can't be put on stronger ones.
+pub struct WeakCards {
+    pub weight: i32,
+}
+
+impl Evaluation for WeakCards {
+    fn evaluate(&self, state: &VerboseState, _rules: &Rules) -> i32 {
+        let weakness = |hand: &CardsHand| {
+            (0..CardsHand::CARD_TYPES)
+                .map(|i| i as i32 * i32::from(hand.cards[i]))
+                .sum::<i32>()
+        };
+        self.weight * (weakness(&state.opponent_hand) - weakness(&state.player_hand))
+    }
+}
+
+/// The stronger the top card of the table stack, the worse for the side to move,
+/// which has to beat it or take.
+pub struct TableTop {
+    pub weight: i32,
+}
+
+impl Evaluation for TableTop {
+    fn evaluate(&self, state: &VerboseState, _rules: &Rules) -> i32 {
+        let strength = match (0..CardsHand::CARD_TYPES).find(|&i| state.table_stack.cards[i] > 0) {
+            Some(top) => (CardsHand::CARD_TYPES - top) as i32,
+            None => 0,
+        };
+        match state.turn {
+            Turn::Player => -self.weight * strength,
+            Turn::Opponent => self.weight * strength,
+        }
+    }
+}
+
+/// Depth-limited negamax with alpha-beta pruning, transposition table and iterative deepening.
+pub struct Minimax<E> {
+    pub rules: Rules,
+    /// In plies.
+    pub max_depth: usize,
+    /// The search stops once the limit is exceeded, using the last finished depth
+    /// (the first one is always finished).
+    pub time_limit: Option<Duration>,
+    pub evaluation: E,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct TableEntry {
+    depth: usize,
+    /// Wins counted in plies from this state, not from the root.
+    score: i32,
+    bound: Bound,
+    best: Option<MoveDescription>,
+}
+
+/// State of one search.
+struct Search {
+    table: HashMap<State, TableEntry>,
+    /// `None` for the first depth, which is always finished.
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl<E: Evaluation> Minimax<E> {
+    /// Score of a win found right away; wins found deeper score less.
+    /// Evaluations have to stay below half of it.
+    const WIN_SCORE: i32 = 1_000_000;
+
+    /// Win scores relative to the state `ply` plies from the root, as kept in the table.
+    fn to_table_score(score: i32, ply: usize) -> i32 {
+        match score {
+            s if s > Self::WIN_SCORE / 2 => s + ply as i32,
+            s if s < -Self::WIN_SCORE / 2 => s - ply as i32,
+            s => s,
+        }
+    }
+
+    /// Inverse of `to_table_score`.
+    fn from_table_score(score: i32, ply: usize) -> i32 {
+        match score {
+            s if s > Self::WIN_SCORE / 2 => s - ply as i32,
+            s if s < -Self::WIN_SCORE / 2 => s + ply as i32,
+            s => s,
+        }
+    }
+
+    pub fn new(rules: Rules, max_depth: usize, evaluation: E) -> Self {
+        Self {
+            rules,
+            max_depth,
+            time_limit: None,
+            evaluation,
+        }
+    }
+
+    /// Score of the state for the side to move.
+    fn negamax(
+        &self,
+        state: &VerboseState,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        search: &mut Search,
+    ) -> i32 {
+        if search.deadline.is_some_and(|d| Instant::now() >= d) {
+            // The result is thrown away.
+            search.timed_out = true;
+            return 0;
+        }
+        if let Some(winner) = state.winner() {
+            let score = Self::WIN_SCORE - ply as i32;
+            return if winner == state.turn { score } else { -score };
+        }
+        if depth == 0 {
+            let score = self.evaluation.evaluate(state, &self.rules);
+            return match state.turn {
+                Turn::Player => score,
+                Turn::Opponent => -score,
+            };
+        }
+
+        let key = State::from_verbose(state, &self.rules).expect("Valid state");
+        let entry = search.table.get(&key).copied();
+        if let Some(e) = entry.filter(|e| e.depth >= depth) {
+            let score = Self::from_table_score(e.score, ply);
+            let usable = match e.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                return score;
+            }
+        }
+
+        let mut moves = state.possible_moves(&self.rules);
+        if moves.is_empty() {
+            return 0;
+        }
+        if let Some(best) = entry.and_then(|e| e.best) {
+            // Try the best move of a shallower search first.
+            if let Some(pos) = moves.iter().position(|m| m.desc == best) {
+                moves.swap(0, pos);
+            }
+        }
+
+        let alpha_orig = alpha;
+        let (mut best_score, mut best_move) = (i32::MIN, None);
+        for m in &moves {
+            let score = -self.negamax(&m.state, depth - 1, ply + 1, -beta, -alpha, search);
+            if search.timed_out {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m.desc);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= alpha_orig {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        search.table.insert(
+            key,
+            TableEntry {
+                depth,
+                score: Self::to_table_score(best_score, ply),
+                bound,
+                best: best_move,
+            },
+        );
+        best_score
+    }
+}
+
+impl<E: Evaluation> Strategy for Minimax<E> {
+    fn get_next_move(&self, state: &VerboseState, _rng: &mut dyn RngCore) -> Option<Move> {
+        let moves = state.possible_moves(&self.rules);
+        let start = Instant::now();
+        let mut search = Search {
+            table: HashMap::new(),
+            deadline: None,
+            timed_out: false,
+        };
+        let mut best = None;
+
+        for depth in 1..=self.max_depth.max(1) {
+            let (mut alpha, beta) = (-i32::MAX, i32::MAX);
+            let mut depth_best = None;
+            let mut ordered = moves.iter().collect::<Vec<_>>();
+            if let Some(pos) = best.and_then(|b| moves.iter().position(|m| m.desc == b)) {
+                ordered.swap(0, pos);
+            }
+            for m in ordered {
+                let score = -self.negamax(&m.state, depth - 1, 1, -beta, -alpha, &mut search);
+                if search.timed_out {
+                    break;
+                }
+                if depth_best.is_none() || score > alpha {
+                    alpha = score;
+                    depth_best = Some(m.desc);
+                }
+            }
+            if search.timed_out {
+                break;
+            }
+            best = depth_best;
+            search.deadline = self.time_limit.map(|limit| start + limit);
+        }
+
+        let best = best?;
+        moves.into_iter().find(|m| m.desc == best)
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    rules::Rules,
+    state::{State, Turn, VerboseState},
+    strategy::{OptimalCache, Outcome},
+};
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU8, Ordering},
+    thread,
+};
+
+// Status of a state in the solved region.
+/// Not to be solved: unreachable or decided before.
+const OUTSIDE: u8 = 0;
+const UNDECIDED: u8 = 1;
+const DECIDED: u8 = 2;
+/// Decided before and already queued for propagation.
+const QUEUED: u8 = 3;
+
+/// Frontiers smaller than this are not worth spawning threads for.
+const MIN_PARALLEL_LEN: usize = 1024;
+
+/// Retrograde analysis of a region of states, run on several threads.
+///
+/// States are decided level by level, in order of growing plies to the end of the game.
+/// A state is won once any following state is won for its mover, and lost once all following
+/// states are lost, which is tracked by an atomic counter of following states per state.
+/// States never decided are draws.
+struct Solver<'c> {
+    cache: &'c OptimalCache,
+    rules: Rules,
+    threads: usize,
+    status: Vec<AtomicU8>,
+    /// Following states not lost for the mover yet.
+    remaining: Vec<AtomicU8>,
+}
+
+impl<'c> Solver<'c> {
+    fn new(cache: &'c OptimalCache) -> Self {
+        let states_cnt = State::count(&cache.rules);
+        Self {
+            cache,
+            rules: cache.rules,
+            threads: cache.threads,
+            status: (0..states_cnt).map(|_| AtomicU8::new(OUTSIDE)).collect(),
+            remaining: (0..states_cnt).map(|_| AtomicU8::new(0)).collect(),
+        }
+    }
+
+    /// Distinct following states.
+    fn following_states(&self, s: State) -> Vec<State> {
+        let mut res = VerboseState::from_state(s, &self.rules)
+            .following_states(&self.rules)
+            .iter()
+            .map(|vs| State::from_verbose(vs, &self.rules).unwrap())
+            .collect::<Vec<_>>();
+        res.sort_unstable_by_key(|s| s.index(&self.rules));
+        res.dedup();
+        res
+    }
+
+    /// Distinct preceding states.
+    fn preceding_states(&self, s: State) -> Vec<State> {
+        let mut res = VerboseState::from_state(s, &self.rules)
+            .preceding_states(&self.rules)
+            .iter()
+            .map(|vs| State::from_verbose(vs, &self.rules).unwrap())
+            .collect::<Vec<_>>();
+        res.sort_unstable_by_key(|s| s.index(&self.rules));
+        res.dedup();
+        res
+    }
+
+    /// Adds a state to the region, if it is not there yet.
+    /// Returns what was found out about it: its outcome if the game is finished or it was
+    /// decided before, `None` if it needs solving.
+    fn claim(&self, s: State) -> Option<Option<Outcome>> {
+        let idx = s.index(&self.rules);
+        if let Some(outcome) = self.cache.get_state_outcome(s) {
+            // Draws decided before cannot decide anything.
+            let queue = outcome.winner.is_some()
+                && self.status[idx].swap(QUEUED, Ordering::Relaxed) != QUEUED;
+            return queue.then_some(Some(outcome));
+        }
+        if self.status[idx]
+            .compare_exchange(OUTSIDE, UNDECIDED, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        if let Some(winner) = VerboseState::from_state(s, &self.rules).winner() {
+            self.status[idx].store(DECIDED, Ordering::Relaxed);
+            return Some(Some(Outcome {
+                winner: Some(winner),
+                plies: 0,
+            }));
+        }
+        let following_cnt = self.following_states(s).len();
+        self.remaining[idx].store(u8::try_from(following_cnt).unwrap(), Ordering::Relaxed);
+        Some(None)
+    }
+
+    /// States preceding the ones with given winner decided in `plies` plies,
+    /// which get decided by them.
+    fn propagate(&self, s: State, winner: Turn, plies: u16, decided: &mut Vec<(State, Outcome)>) {
+        let outcome = Outcome {
+            winner: Some(winner),
+            plies: plies + 1,
+        };
+        for prev in self.preceding_states(s) {
+            let idx = prev.index(&self.rules);
+            if self.status[idx].load(Ordering::Relaxed) != UNDECIDED {
+                continue;
+            }
+            // Losing, unless this was the last following state not lost yet.
+            let decides =
+                prev.turn() == winner || self.remaining[idx].fetch_sub(1, Ordering::Relaxed) == 1;
+            if decides
+                && self.status[idx]
+                    .compare_exchange(UNDECIDED, DECIDED, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                decided.push((prev, outcome));
+            }
+        }
+    }
+
+    /// Decides the region level by level, starting from the already decided `seeds`.
+    /// Returns all decided states in the region, draws included.
+    fn solve(&self, seeds: Vec<(State, Outcome)>) -> Vec<(State, Outcome)> {
+        let mut levels = BTreeMap::<u16, Vec<(State, Turn)>>::new();
+        let mut res = vec![];
+        for (s, outcome) in seeds {
+            let winner = outcome.winner.expect("Decided seed");
+            levels.entry(outcome.plies).or_default().push((s, winner));
+            if self.status[s.index(&self.rules)].load(Ordering::Relaxed) == DECIDED {
+                res.push((s, outcome));
+            }
+        }
+
+        while let Some((plies, level)) = levels.pop_first() {
+            let decided = par_flat_map(level.len(), self.threads, |i, out| {
+                let (s, winner) = level[i];
+                self.propagate(s, winner, plies, out);
+            });
+            if !decided.is_empty() {
+                levels
+                    .entry(plies + 1)
+                    .or_default()
+                    .extend(decided.iter().map(|(s, o)| (*s, o.winner.unwrap())));
+                res.extend(decided);
+            }
+        }
+
+        let draws = par_flat_map(self.status.len(), self.threads, |idx, out| {
+            if self.status[idx].load(Ordering::Relaxed) == UNDECIDED {
+                let draw = Outcome {
+                    winner: None,
+                    plies: 0,
+                };
+                out.push((State::from_index(idx, &self.rules), draw));
+            }
+        });
+        res.extend(draws);
+        res
+    }
+}
+
+/// Solves states reachable from `start_state` and unknown to the cache.
+pub(super) fn solve_reachable(cache: &mut OptimalCache, start_state: State) {
+    let solver = Solver::new(cache);
+    let mut seeds = vec![];
+
+    // Phase 1: find all reachable states (unknown to already built cache), frontier by frontier.
+    let mut frontier = vec![];
+    match solver.claim(start_state) {
+        Some(Some(outcome)) => seeds.push((start_state, outcome)),
+        Some(None) => frontier.push(start_state),
+        None => {}
+    }
+    while !frontier.is_empty() {
+        let found = par_flat_map(frontier.len(), solver.threads, |i, out| {
+            for s in solver.following_states(frontier[i]) {
+                if let Some(outcome) = solver.claim(s) {
+                    out.push((s, outcome));
+                }
+            }
+        });
+        frontier.clear();
+        for (s, outcome) in found {
+            match outcome {
+                Some(outcome) => seeds.push((s, outcome)),
+                None => frontier.push(s),
+            }
+        }
+    }
+
+    // Phase 2: propagate down winning states.
+    let decided = solver.solve(seeds);
+    for (s, outcome) in decided {
+        cache.insert(s, outcome.winner, outcome.plies);
+    }
+}
+
+/// Solves all valid states unknown to the cache (see `VerboseState::validate`).
+pub(super) fn solve_all(cache: &mut OptimalCache) {
+    let solver = Solver::new(cache);
+
+    // Phase 1: every valid state is in the region. Invalid ones are left unknown,
+    // as no valid state precedes them.
+    let seeds = par_flat_map(State::count(&solver.rules), solver.threads, |idx, out| {
+        let s = State::from_index(idx, &solver.rules);
+        if VerboseState::from_state(s, &solver.rules)
+            .validate(&solver.rules)
+            .is_err()
+        {
+            return;
+        }
+        if let Some(Some(outcome)) = solver.claim(s) {
+            out.push((s, outcome));
+        }
+    });
+
+    // Phase 2: propagate down winning states.
+    let decided = solver.solve(seeds);
+    for (s, outcome) in decided {
+        cache.insert(s, outcome.winner, outcome.plies);
+    }
+}
+
+/// Calls `f` for every index in `0..len` on up to `threads` threads,
+/// concatenating what the calls push.
+pub(super) fn par_flat_map<R: Send>(
+    len: usize,
+    threads: usize,
+    f: impl Fn(usize, &mut Vec<R>) + Sync,
+) -> Vec<R> {
+    if threads <= 1 || len < MIN_PARALLEL_LEN {
+        let mut out = vec![];
+        (0..len).for_each(|i| f(i, &mut out));
+        return out;
+    }
+
+    let chunk_len = len.div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles = (0..len)
+            .step_by(chunk_len)
+            .map(|start| {
+                scope.spawn(move || {
+                    let mut out = vec![];
+                    (start..len.min(start + chunk_len)).for_each(|i| f(i, &mut out));
+                    out
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("Solver thread panicked"))
+            .collect()
+    })
+}
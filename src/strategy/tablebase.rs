@@ -0,0 +1,202 @@
+use crate::{
+    rules::Rules,
+    state::{State, Turn},
+    strategy::Outcome,
+};
+use memmap2::Mmap;
+
+/// Outcomes of all states under given rules, indexed by `State::index`.
+///
+/// Kept in the layout of the cache file: a section of 2-bit verdicts (four per byte, lowest
+/// bits first; 3.8 MB under the standard rules), optionally followed by a section of
+/// little-endian `u16` plies to the end for every state, unknown and drawn ones included
+/// (30 MB more). Who wins is read from the verdicts alone, so without the plies, or with
+/// them mapped but never paged in, lookups use the small table; the plies are needed for
+/// optimal play and solving. Lookups are answered from a memory-mapped file directly;
+/// the tablebase is copied only once changed.
+pub(super) struct Tablebase {
+    states_cnt: usize,
+    storage: Storage,
+    known_cnt: usize,
+}
+
+/// Clones are kept in memory.
+impl Clone for Tablebase {
+    fn clone(&self) -> Self {
+        Self {
+            states_cnt: self.states_cnt,
+            storage: Storage::Owned(self.storage.bytes().to_vec()),
+            known_cnt: self.known_cnt,
+        }
+    }
+}
+
+enum Storage {
+    Owned(Vec<u8>),
+    /// The tablebase starts at `offset` in the mapped file.
+    Mapped {
+        mmap: Mmap,
+        offset: usize,
+    },
+}
+
+impl Storage {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Storage::Owned(bytes) => bytes,
+            Storage::Mapped { mmap, offset } => &mmap[*offset..],
+        }
+    }
+}
+
+const UNKNOWN: u8 = 0;
+const PLAYER_WINS: u8 = 1;
+const OPPONENT_WINS: u8 = 2;
+const DRAW: u8 = 3;
+
+impl Tablebase {
+    /// Empty tablebase with plies.
+    pub fn new(rules: &Rules) -> Self {
+        let states_cnt = State::count(rules);
+        Self {
+            states_cnt,
+            storage: Storage::Owned(vec![0; Self::bytes_len(states_cnt, true)]),
+            known_cnt: 0,
+        }
+    }
+
+    fn verdicts_len(states_cnt: usize) -> usize {
+        states_cnt.div_ceil(4)
+    }
+
+    fn bytes_len(states_cnt: usize, with_plies: bool) -> usize {
+        let plies_len = if with_plies { 2 * states_cnt } else { 0 };
+        Self::verdicts_len(states_cnt) + plies_len
+    }
+
+    pub fn has_plies(&self) -> bool {
+        self.storage.bytes().len() == Self::bytes_len(self.states_cnt, true)
+    }
+
+    /// Keeps only the verdicts.
+    pub fn drop_plies(&mut self) {
+        let verdicts_len = Self::verdicts_len(self.states_cnt);
+        match &mut self.storage {
+            Storage::Owned(bytes) => bytes.truncate(verdicts_len),
+            Storage::Mapped { .. } => {
+                self.storage = Storage::Owned(self.storage.bytes()[..verdicts_len].to_vec());
+            }
+        }
+    }
+
+    pub fn states_cnt(&self) -> usize {
+        self.states_cnt
+    }
+
+    /// Number of states with known outcome.
+    pub fn len(&self) -> usize {
+        self.known_cnt
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.storage, Storage::Mapped { .. })
+    }
+
+    fn verdict(bytes: &[u8], idx: usize) -> u8 {
+        (bytes[idx / 4] >> (idx % 4 * 2)) & 0b11
+    }
+
+    /// `None` if unknown, `Some(None)` for a draw.
+    pub fn winner(&self, idx: usize) -> Option<Option<Turn>> {
+        match Self::verdict(self.storage.bytes(), idx) {
+            UNKNOWN => None,
+            PLAYER_WINS => Some(Some(Turn::Player)),
+            OPPONENT_WINS => Some(Some(Turn::Opponent)),
+            _ => Some(None),
+        }
+    }
+
+    /// `None` if unknown or without plies.
+    pub fn get(&self, idx: usize) -> Option<Outcome> {
+        let winner = self.winner(idx)?;
+        if !self.has_plies() {
+            return None;
+        }
+        let bytes = self.storage.bytes();
+        let plies_pos = Self::verdicts_len(self.states_cnt) + 2 * idx;
+        Some(Outcome {
+            winner,
+            plies: u16::from_le_bytes([bytes[plies_pos], bytes[plies_pos + 1]]),
+        })
+    }
+
+    /// Plies are left out without them.
+    pub fn set(&mut self, idx: usize, outcome: Outcome) {
+        let verdict = match outcome.winner {
+            Some(Turn::Player) => PLAYER_WINS,
+            Some(Turn::Opponent) => OPPONENT_WINS,
+            None => DRAW,
+        };
+        let plies_pos = Self::verdicts_len(self.states_cnt) + 2 * idx;
+        let has_plies = self.has_plies();
+        let bytes = self.bytes_mut();
+
+        let shift = idx % 4 * 2;
+        let known = Self::verdict(bytes, idx) != UNKNOWN;
+        bytes[idx / 4] = (bytes[idx / 4] & !(0b11 << shift)) | (verdict << shift);
+        if has_plies {
+            bytes[plies_pos..plies_pos + 2].copy_from_slice(&outcome.plies.to_le_bytes());
+        }
+        if !known {
+            self.known_cnt += 1;
+        }
+    }
+
+    /// Copies a mapped tablebase first.
+    fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        if let Storage::Mapped { .. } = self.storage {
+            self.storage = Storage::Owned(self.storage.bytes().to_vec());
+        }
+        match &mut self.storage {
+            Storage::Owned(bytes) => bytes,
+            Storage::Mapped { .. } => unreachable!(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> &[u8] {
+        self.storage.bytes()
+    }
+
+    /// Inverse of `to_bytes`.
+    pub fn from_bytes(bytes: &[u8], with_plies: bool, rules: &Rules) -> Result<Self, String> {
+        Self::from_storage(Storage::Owned(bytes.to_vec()), with_plies, rules)
+    }
+
+    /// Tablebase at `offset` of the mapped cache file.
+    pub fn from_mmap(
+        mmap: Mmap,
+        offset: usize,
+        with_plies: bool,
+        rules: &Rules,
+    ) -> Result<Self, String> {
+        Self::from_storage(Storage::Mapped { mmap, offset }, with_plies, rules)
+    }
+
+    fn from_storage(storage: Storage, with_plies: bool, rules: &Rules) -> Result<Self, String> {
+        let states_cnt = State::count(rules);
+        let bytes = storage.bytes();
+        if bytes.len() != Self::bytes_len(states_cnt, with_plies) {
+            return Err("Malformed tablebase (unexpected size).".to_string());
+        }
+
+        // Only the verdicts are read, the plies are left to be paged in when needed.
+        let known_cnt = (0..states_cnt)
+            .filter(|&idx| Self::verdict(bytes, idx) != UNKNOWN)
+            .count();
+        Ok(Self {
+            states_cnt,
+            storage,
+            known_cnt,
+        })
+    }
+}
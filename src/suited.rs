@@ -0,0 +1,280 @@
+use crate::{
+    rules::Rules,
+    state::{CardsHand, MoveDescription, State, Turn, VerboseState},
+};
+use std::fmt;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Ord, PartialOrd)]
+pub enum Suit {
+    Hearts,
+    Diamonds,
+    Clubs,
+    Spades,
+}
+
+impl Suit {
+    pub const ALL: [Suit; 4] = [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades];
+
+    pub fn to_char(self) -> char {
+        match self {
+            Suit::Hearts => '♥',
+            Suit::Diamonds => '♦',
+            Suit::Clubs => '♣',
+            Suit::Spades => '♠',
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Ord, PartialOrd)]
+pub struct Card {
+    /// Index of the rank, as in `CardsHand::cards`.
+    pub rank: usize,
+    pub suit: Suit,
+}
+
+impl Card {
+    pub const NINE_OF_HEARTS: Self = Self {
+        rank: CardsHand::NINES_IDX,
+        suit: Suit::Hearts,
+    };
+
+    fn bit(self) -> u32 {
+        1 << (self.rank * Suit::ALL.len() + self.suit as usize)
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rank = CardsHand::IDX_TO_CHAR[self.rank];
+        write!(f, "{}{}", rank, self.suit.to_char())
+    }
+}
+
+/// Set of suited cards.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct SuitedHand {
+    bits: u32,
+}
+
+impl SuitedHand {
+    pub const EMPTY: Self = Self { bits: 0 };
+
+    /// All 24 cards.
+    pub fn full_deck() -> Self {
+        Self {
+            bits: (1 << (CardsHand::CARD_TYPES * Suit::ALL.len())) - 1,
+        }
+    }
+
+    pub fn contains(&self, card: Card) -> bool {
+        self.bits & card.bit() != 0
+    }
+
+    pub fn insert(&mut self, card: Card) {
+        self.bits |= card.bit();
+    }
+
+    pub fn remove(&mut self, card: Card) {
+        self.bits &= !card.bit();
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Cards sorted from the strongest rank, hearts first within a rank.
+    pub fn iter(&self) -> impl Iterator<Item = Card> + '_ {
+        (0..CardsHand::CARD_TYPES).flat_map(move |rank| self.cards_of_rank(rank))
+    }
+
+    pub fn cards_of_rank(&self, rank: usize) -> impl Iterator<Item = Card> + '_ {
+        Suit::ALL
+            .iter()
+            .map(move |&suit| Card { rank, suit })
+            .filter(move |&c| self.contains(c))
+    }
+
+    /// Forgets the suits.
+    pub fn to_cards_hand(&self) -> CardsHand {
+        let mut hand = CardsHand::EMPTY;
+        for c in self.iter() {
+            hand.cards[c.rank] += 1;
+        }
+        hand
+    }
+}
+
+impl FromIterator<Card> for SuitedHand {
+    fn from_iter<I: IntoIterator<Item = Card>>(iter: I) -> Self {
+        let mut hand = Self::EMPTY;
+        for c in iter {
+            hand.insert(c);
+        }
+        hand
+    }
+}
+
+/// Like `VerboseState`, but keeps track of suits of all cards.
+///
+/// When the bottom nine is fixed (`Rules::nine_on_table` or `Rules::is_bottom_nine_fixed`),
+/// it is the nine of hearts and it is stored explicitly at the bottom of `table_stack`.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct SuitedState {
+    pub player_hand: SuitedHand,
+    pub opponent_hand: SuitedHand,
+    /// From the bottom to the top.
+    pub table_stack: Vec<Card>,
+    pub turn: Turn,
+}
+
+#[derive(Clone, Debug)]
+pub struct SuitedMove {
+    pub state: SuitedState,
+    pub desc: MoveDescription,
+    /// Cards put or taken, in order they were put or taken.
+    pub cards: Vec<Card>,
+}
+
+impl SuitedState {
+    pub fn possible_moves(&self, rules: &Rules) -> Vec<SuitedMove> {
+        let mut res = vec![];
+        if self.is_game_finished() {
+            return res;
+        }
+
+        let cur_hand = *self.get_current_hand();
+        let put_cards = |cards: Vec<Card>, desc: MoveDescription| {
+            let mut s = self.clone();
+            for &c in &cards {
+                s.get_current_hand_mut().remove(c);
+                s.table_stack.push(c);
+            }
+            s.turn = s.turn.next();
+            SuitedMove {
+                state: s,
+                desc,
+                cards,
+            }
+        };
+
+        if self.table_stack.is_empty() && Self::is_bottom_nine_fixed(rules) {
+            // Opening with the nine of hearts
+            if cur_hand.contains(Card::NINE_OF_HEARTS) {
+                let desc = MoveDescription::PutSingle(CardsHand::NINES_IDX);
+                res.push(put_cards(vec![Card::NINE_OF_HEARTS], desc));
+            }
+            return res;
+        }
+
+        let top_rank = self.table_stack.last().map(|c| c.rank);
+        for i in 0..CardsHand::CARD_TYPES {
+            if top_rank.is_some_and(|top| i > top) {
+                // Can't put weaker cards
+                break;
+            }
+
+            let cards = cur_hand.cards_of_rank(i).collect::<Vec<_>>();
+            let all_cards_cnt = rules.card_idx_to_cnt(i);
+
+            // Put 1 card
+            for &c in &cards {
+                res.push(put_cards(vec![c], MoveDescription::PutSingle(i)));
+            }
+
+            // Put 3 cards (if not all of them)
+            if rules.allow_put_three && all_cards_cnt != 3 {
+                if cards.len() == 3 {
+                    res.push(put_cards(cards.clone(), MoveDescription::PutThree(i)));
+                } else if cards.len() == 4 {
+                    // Any card can be kept in hand
+                    for kept in 0..cards.len() {
+                        let mut put = cards.clone();
+                        put.remove(kept);
+                        res.push(put_cards(put, MoveDescription::PutThree(i)));
+                    }
+                }
+            }
+
+            // Put 4 cards (or 3 nines)
+            if cards.len() == all_cards_cnt {
+                res.push(put_cards(cards, MoveDescription::PutAll(i)));
+            }
+        }
+
+        {
+            // Take up to 3 top cards
+            let fixed_cnt = usize::from(Self::is_bottom_nine_fixed(rules));
+            let takeable_cnt = self.table_stack.len().saturating_sub(fixed_cnt);
+            let taken_cnt = takeable_cnt.min(usize::from(rules.cards_cnt_when_taking_from_stack));
+            if taken_cnt > 0 {
+                let mut s = self.clone();
+                let mut cards = vec![];
+                for _ in 0..taken_cnt {
+                    let c = s.table_stack.pop().unwrap();
+                    s.get_current_hand_mut().insert(c);
+                    cards.push(c);
+                }
+                s.turn = s.turn.next();
+                res.push(SuitedMove {
+                    state: s,
+                    desc: MoveDescription::Take,
+                    cards,
+                });
+            }
+        }
+
+        res
+    }
+
+    pub fn is_game_finished(&self) -> bool {
+        self.player_hand.is_empty() || self.opponent_hand.is_empty()
+    }
+
+    /// Forgets the suits (and the table nine if it is out of play).
+    pub fn to_verbose(&self, rules: &Rules) -> Result<VerboseState, &'static str> {
+        let mut table_stack = self.table_stack.as_slice();
+        if rules.nine_on_table {
+            match table_stack.split_first() {
+                Some((&Card::NINE_OF_HEARTS, rest)) => table_stack = rest,
+                _ => return Err("Nine of hearts must lie at the bottom of the table stack"),
+            }
+        }
+
+        Ok(VerboseState {
+            player_hand: self.player_hand.to_cards_hand(),
+            opponent_hand: self.opponent_hand.to_cards_hand(),
+            table_stack: table_stack
+                .iter()
+                .copied()
+                .collect::<SuitedHand>()
+                .to_cards_hand(),
+            turn: self.turn,
+        })
+    }
+
+    pub fn to_state(&self, rules: &Rules) -> Result<State, &'static str> {
+        State::from_verbose(&self.to_verbose(rules)?, rules)
+    }
+
+    fn is_bottom_nine_fixed(rules: &Rules) -> bool {
+        rules.nine_on_table || rules.is_bottom_nine_fixed()
+    }
+
+    fn get_current_hand(&self) -> &SuitedHand {
+        match self.turn {
+            Turn::Player => &self.player_hand,
+            Turn::Opponent => &self.opponent_hand,
+        }
+    }
+
+    fn get_current_hand_mut(&mut self) -> &mut SuitedHand {
+        match self.turn {
+            Turn::Player => &mut self.player_hand,
+            Turn::Opponent => &mut self.opponent_hand,
+        }
+    }
+}